@@ -0,0 +1,63 @@
+//! Method lookup in `MapRouter` and `FastRouter`, with the same routes registered in both
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use jsonrpc_v2::router::{FastRouter, MapRouter, Route, Router};
+use jsonrpc_v2::Error;
+
+async fn handler(_: ()) -> Result<(), Error> {
+    Ok(())
+}
+
+/// `count` methods named like those of an Ethereum node, e.g. `eth_method7`
+fn names(count: usize) -> Vec<String> {
+    let namespaces = ["eth", "net", "web3", "debug", "admin", "personal", "txpool", "trace"];
+    (0..count).map(|i| format!("{}_method{}", namespaces[i % namespaces.len()], i)).collect()
+}
+
+fn routes(names: &[String]) -> Vec<(String, Route<()>)> {
+    names.iter().map(|name| (name.clone(), Route::new(handler))).collect()
+}
+
+fn fill<R: Router<()>>(routes: &[(String, Route<()>)]) -> R {
+    let mut router = R::default();
+    for (name, route) in routes {
+        router.insert(name.clone(), route.clone());
+    }
+    router
+}
+
+fn lookup(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lookup");
+
+    for &count in &[16, 256] {
+        let names = names(count);
+        let routes = routes(&names);
+        let map: MapRouter<()> = fill(&routes);
+        let fast: FastRouter<()> = fill(&routes);
+
+        group.bench_with_input(BenchmarkId::new("MapRouter", count), &names, |b, names| {
+            b.iter(|| names.iter().filter(|name| map.get(black_box(name)).is_some()).count())
+        });
+        group.bench_with_input(BenchmarkId::new("FastRouter", count), &names, |b, names| {
+            b.iter(|| names.iter().filter(|name| fast.get(black_box(name)).is_some()).count())
+        });
+    }
+
+    group.finish();
+}
+
+fn wildcard(c: &mut Criterion) {
+    let patterns = ["eth_*", "debug_trace*", "txpool_*"];
+    let mut all = routes(&names(256));
+    all.extend(routes(&patterns.iter().map(|pattern| pattern.to_string()).collect::<Vec<_>>()));
+    let fast: FastRouter<()> = fill(&all);
+
+    // no exact match, so each goes through the patterns
+    let names = ["eth_unknown", "debug_traceCall", "txpool_status", "unknown_method"];
+    c.bench_function("wildcard/FastRouter", |b| {
+        b.iter(|| names.iter().filter(|name| fast.get(black_box(name)).is_some()).count())
+    });
+}
+
+criterion_group!(benches, lookup, wildcard);
+criterion_main!(benches);
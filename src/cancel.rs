@@ -0,0 +1,80 @@
+//! Cancellation of in-flight requests on stateful transports
+
+use crate::error::Error;
+use crate::request::{FromRequest, ParamsSchema, RequestObject};
+use crate::server::Metadata;
+use crate::Id;
+
+use futures::channel::oneshot;
+use futures::future::{self, FutureExt, Shared};
+use serde::Deserialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Reserved method of the notification that cancels an in-flight request
+///
+/// Its params are `{"id": <id of the request to cancel>}`. The cancelled request is answered with
+/// [`Error::REQUEST_CANCELLED`](../enum.Error.html#associatedconstant.REQUEST_CANCELLED).
+/// Only stateful transports intercept it; elsewhere it is routed like any other method.
+pub const CANCEL_METHOD: &str = "$/cancelRequest";
+
+#[derive(Deserialize)]
+pub(crate) struct CancelParams {
+    pub(crate) id: Id,
+}
+
+/// [`FromRequest`](../trait.FromRequest.html) handle to observe whether the client has cancelled the request
+///
+/// Once a request is cancelled its handler future is dropped, so the token is mainly useful for
+/// work the handler hands off, e.g. to a spawned task or a blocking thread. Outside of stateful
+/// transports, and for notifications, the token is never cancelled.
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+    signal: Option<Shared<oneshot::Receiver<()>>>,
+}
+
+impl CancellationToken {
+    /// Whether the client has cancelled the request
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Acquire)
+    }
+
+    /// Resolves once the client has cancelled the request; never resolves otherwise
+    pub async fn cancelled(&self) {
+        match self.signal {
+            Some(ref signal) if signal.clone().await.is_ok() => {}
+            _ => future::pending().await,
+        }
+    }
+}
+
+impl ParamsSchema for CancellationToken {}
+
+#[async_trait::async_trait]
+impl<M: Metadata> FromRequest<M> for CancellationToken {
+    async fn from_request(req: &RequestObject, _: M) -> Result<Self, Error> {
+        Ok(req.extensions.get::<CancellationToken>().cloned().unwrap_or_default())
+    }
+}
+
+/// Triggers the matching [`CancellationToken`](struct.CancellationToken.html)
+pub(crate) struct CancelHandle {
+    cancelled: Arc<AtomicBool>,
+    signal: oneshot::Sender<()>,
+}
+
+impl CancelHandle {
+    pub(crate) fn new() -> (Self, CancellationToken) {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let (tx, rx) = oneshot::channel();
+
+        let handle = CancelHandle { cancelled: Arc::clone(&cancelled), signal: tx };
+        (handle, CancellationToken { cancelled, signal: Some(rx.shared()) })
+    }
+
+    pub(crate) fn cancel(self) {
+        self.cancelled.store(true, Ordering::Release);
+        let _ = self.signal.send(());
+    }
+}
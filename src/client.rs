@@ -0,0 +1,375 @@
+//! A typed JSON-RPC 2.0 client over a pluggable [`Transport`](trait.Transport.html)
+
+use crate::error::Error;
+use crate::request::RequestObject;
+use crate::response::ResponseObjects;
+use crate::server::{Metadata, Server};
+use crate::{BoxedSerialize, Id};
+
+use futures::channel::oneshot;
+use futures::future::{Future, FutureExt};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+#[cfg(not(feature = "bytes-v04"))]
+use bytes::Bytes;
+
+#[cfg(feature = "bytes-v04")]
+use bytes_v04::Bytes;
+
+/// Boxed error returned by a [`Transport`](trait.Transport.html)
+pub type TransportError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// Sends serialized requests to a server
+#[async_trait::async_trait]
+pub trait Transport: Send + Sync {
+    /// Send a serialized request or batch, returning the serialized response,
+    /// or `None` if the server sent nothing back (e.g. for notifications)
+    async fn send(&self, request: Vec<u8>) -> Result<Option<Vec<u8>>, TransportError>;
+}
+
+/// In-process transport that hands requests straight to a [`Server`](../struct.Server.html)
+///
+/// Requests go through the same parsing, middlewares, handlers and serialization as over the
+/// network, which makes it convenient for testing a `Server` with typed results and errors:
+///
+/// ```rust
+/// # use jsonrpc_v2::{Error, Params, Server};
+/// # use jsonrpc_v2::client::{Client, LoopbackTransport};
+/// #[derive(serde::Deserialize, paperclip::actix::Apiv2Schema)]
+/// struct TwoNums {
+///     a: usize,
+///     b: usize,
+/// }
+///
+/// async fn add(Params(params): Params<TwoNums>, _: ()) -> Result<usize, Error> {
+///     Ok(params.a + params.b)
+/// }
+///
+/// # futures::executor::block_on(async {
+/// let server = Server::new(vec![]).with_method("add", add).finish();
+/// let client = Client::new(LoopbackTransport::new(server, ()));
+///
+/// let sum: usize = client.request("add", serde_json::json!({ "a": 2, "b": 3 })).await.unwrap();
+/// assert_eq!(sum, 5);
+/// # });
+/// ```
+pub struct LoopbackTransport<M>
+where
+    M: Metadata,
+{
+    server: Arc<Server<M>>,
+    metadata: M,
+}
+
+impl<M> LoopbackTransport<M>
+where
+    M: Metadata,
+{
+    /// `metadata` is cloned for every request
+    pub fn new(server: Arc<Server<M>>, metadata: M) -> Self {
+        LoopbackTransport { server, metadata }
+    }
+
+    /// The server requests are sent to
+    pub fn server(&self) -> &Arc<Server<M>> {
+        &self.server
+    }
+}
+
+#[async_trait::async_trait]
+impl<M> Transport for LoopbackTransport<M>
+where
+    M: Metadata + Sync,
+{
+    async fn send(&self, request: Vec<u8>) -> Result<Option<Vec<u8>>, TransportError> {
+        match self.server.handle(Bytes::from(request), self.metadata.clone()).await {
+            ResponseObjects::Empty => Ok(None),
+            res => Ok(Some(serde_json::to_vec(&res)?)),
+        }
+    }
+}
+
+/// Error returned by [`Client`](struct.Client.html) calls
+#[derive(Debug)]
+pub enum ClientError {
+    /// The transport failed to deliver the request or receive the response
+    Transport(TransportError),
+    /// The server responded with an error object
+    Rpc(Error),
+    /// Params could not be serialized, or the response or its `result` could not be deserialized
+    Json(serde_json::Error),
+    /// The server sent no response for the request's id
+    MissingResponse,
+}
+
+impl std::fmt::Display for ClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClientError::Transport(e) => write!(f, "transport error: {}", e),
+            ClientError::Rpc(e) => write!(f, "server error {}: {}", e.code(), e.message()),
+            ClientError::Json(e) => write!(f, "json error: {}", e),
+            ClientError::MissingResponse => write!(f, "no response for request"),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ClientError::Transport(e) => Some(&**e),
+            ClientError::Json(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for ClientError {
+    fn from(t: serde_json::Error) -> Self {
+        ClientError::Json(t)
+    }
+}
+
+/// A response object as received by a client, with the `result` left unparsed
+#[derive(Deserialize)]
+pub(crate) struct RawResponse {
+    #[serde(default)]
+    pub(crate) id: Id,
+    #[serde(default)]
+    result: Option<Box<RawValue>>,
+    #[serde(default)]
+    error: Option<Error>,
+}
+
+impl Clone for RawResponse {
+    fn clone(&self) -> Self {
+        let error = self.error.as_ref().map(|e| Error::Full {
+            code: e.code(),
+            message: e.message().to_owned(),
+            data: e.data().map(|data| Box::new(data) as BoxedSerialize),
+        });
+        RawResponse { id: self.id.clone(), result: self.result.clone(), error }
+    }
+}
+
+impl RawResponse {
+    /// Deserialize the `result`, or convert the `error` into a `ClientError`
+    pub(crate) fn into_result<R: DeserializeOwned>(self) -> Result<R, ClientError> {
+        match (self.error, self.result) {
+            (Some(error), _) => Err(ClientError::Rpc(error)),
+            (None, Some(result)) => Ok(serde_json::from_str(result.get())?),
+            (None, None) => Ok(serde_json::from_value(Value::Null)?),
+        }
+    }
+}
+
+impl RawResponse {
+    /// Parse a single response or a batch of responses
+    pub(crate) fn from_slice_many(slice: &[u8]) -> Result<Vec<Self>, serde_json::Error> {
+        if slice.iter().find(|x| !x.is_ascii_whitespace()) == Some(&b'[') {
+            serde_json::from_slice(slice)
+        } else {
+            Ok(vec![serde_json::from_slice(slice)?])
+        }
+    }
+}
+
+/// JSON-RPC 2.0 client
+///
+/// Allocates request ids, matches responses to requests by id,
+/// and deserializes `result`s into the caller's type.
+pub struct Client<T> {
+    transport: T,
+    next_id: AtomicI64,
+}
+
+impl<T> Client<T>
+where
+    T: Transport,
+{
+    pub fn new(transport: T) -> Self {
+        Client { transport, next_id: AtomicI64::new(1) }
+    }
+
+    /// The underlying transport
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub(crate) fn next_id(&self) -> Id {
+        Id::Num(self.next_id.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// Call `method` with `params`, deserializing the `result` into `R`
+    ///
+    /// `params` should serialize to an array (by-position) or a map (by-name); `()` sends no params.
+    pub async fn request<P, R>(&self, method: &str, params: P) -> Result<R, ClientError>
+    where
+        P: Serialize,
+        R: DeserializeOwned,
+    {
+        let id = self.next_id();
+        let req = request_object(method, params, Some(id.clone()))?;
+
+        let bytes = self.send(&req).await?.ok_or(ClientError::MissingResponse)?;
+
+        let mut responses = RawResponse::from_slice_many(&bytes)?;
+
+        // errors the server could not attribute to a request, e.g. parse errors, carry a null id
+        let pos = responses
+            .iter()
+            .position(|res| res.id == id)
+            .or_else(|| responses.iter().position(|res| res.id.is_null()));
+
+        match pos {
+            Some(pos) => responses.swap_remove(pos).into_result(),
+            None => Err(ClientError::MissingResponse),
+        }
+    }
+
+    /// Start a batch of calls and notifications, sent together as a single payload
+    pub fn batch(&self) -> Batch<'_, T> {
+        Batch { client: self, requests: Vec::new(), pending: HashMap::new() }
+    }
+
+    /// Send a notification: a request without an id, for which the server sends no response
+    pub async fn notify<P>(&self, method: &str, params: P) -> Result<(), ClientError>
+    where
+        P: Serialize,
+    {
+        let req = request_object(method, params, None)?;
+        self.send(&req).await?;
+        Ok(())
+    }
+
+    pub(crate) async fn send<S: Serialize>(&self, req: &S) -> Result<Option<Vec<u8>>, ClientError> {
+        let body = serde_json::to_vec(req)?;
+        self.transport.send(body).await.map_err(ClientError::Transport)
+    }
+}
+
+/// A batch of requests, created with [`Client::batch`](struct.Client.html#method.batch)
+///
+/// Each queued call returns its own [`BatchCall`](struct.BatchCall.html) future, which resolves once
+/// the batch has been sent and the response with the matching id has arrived, regardless of the order
+/// in which the server answers.
+pub struct Batch<'a, T> {
+    client: &'a Client<T>,
+    requests: Vec<RequestObject>,
+    pending: HashMap<Id, oneshot::Sender<RawResponse>>,
+}
+
+impl<'a, T> Batch<'a, T>
+where
+    T: Transport,
+{
+    /// Queue a call to `method`, returning a future for its `result`
+    pub fn request<P, R>(&mut self, method: &str, params: P) -> Result<BatchCall<R>, ClientError>
+    where
+        P: Serialize,
+        R: DeserializeOwned,
+    {
+        let id = self.client.next_id();
+        self.requests.push(request_object(method, params, Some(id.clone()))?);
+
+        let (tx, rx) = oneshot::channel();
+        self.pending.insert(id, tx);
+        Ok(BatchCall { rx, _r: PhantomData })
+    }
+
+    /// Queue a notification
+    pub fn notify<P>(&mut self, method: &str, params: P) -> Result<(), ClientError>
+    where
+        P: Serialize,
+    {
+        self.requests.push(request_object(method, params, None)?);
+        Ok(())
+    }
+
+    /// Send the batch, resolving each queued call's future with its response
+    ///
+    /// An error with a null id, which the server could not attribute to a request, resolves every call
+    /// left without a response of its own; any other call that receives no response resolves with
+    /// `ClientError::MissingResponse`. Sending an empty batch does nothing, as the server would reject it.
+    pub async fn send(self) -> Result<(), ClientError> {
+        let Batch { client, requests, mut pending } = self;
+
+        if requests.is_empty() {
+            return Ok(());
+        }
+
+        if let Some(bytes) = client.send(&requests).await? {
+            let mut unattributed = None;
+            for res in RawResponse::from_slice_many(&bytes)? {
+                if let Some(tx) = pending.remove(&res.id) {
+                    let _ = tx.send(res);
+                } else if res.id.is_null() {
+                    unattributed = Some(res);
+                }
+            }
+
+            // e.g. a parse error, answering the whole payload
+            if let Some(res) = unattributed {
+                for (_, tx) in pending {
+                    let _ = tx.send(res.clone());
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Future for the `result` of a call queued in a [`Batch`](struct.Batch.html)
+pub struct BatchCall<R> {
+    rx: oneshot::Receiver<RawResponse>,
+    _r: PhantomData<fn() -> R>,
+}
+
+impl<R> Future for BatchCall<R>
+where
+    R: DeserializeOwned,
+{
+    type Output = Result<R, ClientError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.rx.poll_unpin(cx).map(|res| match res {
+            Ok(res) => res.into_result(),
+            Err(oneshot::Canceled) => Err(ClientError::MissingResponse),
+        })
+    }
+}
+
+/// Build a request object, or a notification if `id` is `None`
+pub(crate) fn request_object<P: Serialize>(
+    method: &str,
+    params: P,
+    id: Option<Id>,
+) -> Result<RequestObject, ClientError> {
+    let params = serde_json::to_value(params)?;
+
+    Ok(match id {
+        Some(id) => {
+            let builder = RequestObject::request().with_method(method).with_id(id);
+            match params {
+                Value::Null => builder.finish(),
+                params => builder.with_params(params).finish(),
+            }
+        }
+        None => {
+            let builder = RequestObject::notification().with_method(method);
+            match params {
+                Value::Null => builder.finish(),
+                params => builder.with_params(params).finish(),
+            }
+        }
+    })
+}
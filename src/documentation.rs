@@ -2,7 +2,6 @@ use serde::{Deserialize, Serialize};
 
 use paperclip::v2::models::DefaultSchemaRaw;
 
-
 use crate::error::Error;
 use crate::handler::Factory;
 use crate::request::Params;
@@ -19,11 +18,11 @@ pub(crate) struct SpecHandler {
 }
 
 #[async_trait::async_trait]
-impl<M> Factory<(DocType, Notifications), Error, Params<DummyReq>, M> for SpecHandler
+impl<M> Factory<(DocType, Notifications), Error, (Params<DummyReq>,), M> for SpecHandler
 where
     M: Metadata,
 {
-    async fn call(&self, _: Params<DummyReq>, _: M) -> Result<(DocType, Notifications), Error> {
+    async fn call(&self, _: (Params<DummyReq>,), _: M) -> Result<(DocType, Notifications), Error> {
         Ok((self.routes.clone(), self.notifications.clone()))
     }
 }
@@ -1,4 +1,6 @@
-use serde::{Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use std::time::Duration;
 
 use crate::BoxedSerialize;
 
@@ -25,6 +27,15 @@ impl Error {
     pub const INVALID_PARAMS: Self = Error::Provided { code: -32602, message: "Invalid params" };
     pub const INTERNAL_ERROR: Self = Error::Provided { code: -32603, message: "Internal Error" };
     pub const PARSE_ERROR: Self = Error::Provided { code: -32700, message: "Parse error" };
+    /// A subscription method was called over a transport without server-to-client messaging
+    pub const SUBSCRIPTIONS_UNSUPPORTED: Self = Error::Provided {
+        code: -32090,
+        message: "Subscriptions are not available on this transport",
+    };
+
+    /// The client cancelled the request before it completed
+    pub const REQUEST_CANCELLED: Self =
+        Error::Provided { code: -32800, message: "Request cancelled" };
 
     pub fn internal<D: std::fmt::Display + Send>(e: D) -> Self {
         Error::Full {
@@ -33,6 +44,75 @@ impl Error {
             data: Some(Box::new(e.to_string())),
         }
     }
+
+    /// The request was rejected before reaching a handler; `data` explains why
+    pub fn invalid_request<D: std::fmt::Display + Send>(e: D) -> Self {
+        Error::Full {
+            code: -32600,
+            message: "Invalid Request".into(),
+            data: Some(Box::new(e.to_string())),
+        }
+    }
+
+    /// A handler did not complete within its deadline; `data` holds the elapsed time
+    pub fn timeout(elapsed: Duration) -> Self {
+        Error::Full {
+            code: -32001,
+            message: "Request timed out".into(),
+            data: Some(Box::new(serde_json::json!({ "elapsed_ms": elapsed.as_millis() as u64 }))),
+        }
+    }
+
+    /// The `code` field of the error object
+    pub fn code(&self) -> i64 {
+        match self {
+            Error::Full { code, .. } | Error::Provided { code, .. } => *code,
+        }
+    }
+
+    /// The `message` field of the error object
+    pub fn message(&self) -> &str {
+        match self {
+            Error::Full { message, .. } => message,
+            Error::Provided { message, .. } => message,
+        }
+    }
+
+    /// The `data` field of the error object, if any, as a JSON value
+    pub fn data(&self) -> Option<Value> {
+        match self {
+            Error::Full { data: Some(data), .. } => serde_json::to_value(data).ok(),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Debug for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Error")
+            .field("code", &self.code())
+            .field("message", &self.message())
+            .field("data", &self.data())
+            .finish()
+    }
+}
+
+impl<'de> Deserialize<'de> for Error {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct ErrorObject {
+            code: i64,
+            message: String,
+            #[serde(default)]
+            data: Option<Value>,
+        }
+
+        let ErrorObject { code, message, data } = ErrorObject::deserialize(deserializer)?;
+        Ok(Error::Full { code, message, data: data.map(|x| Box::new(x) as BoxedSerialize) })
+    }
 }
 
 /// Trait that can be used to map custom errors to the [`Error`](enum.Error.html) object.
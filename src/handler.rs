@@ -19,6 +19,7 @@ where
     F: Factory<S, E, T, M>,
 {
     hnd: F,
+    #[allow(clippy::type_complexity)]
     _t: PhantomData<fn() -> (S, E, T, M)>,
 }
 
@@ -31,21 +32,36 @@ where
     }
 }
 
-#[async_trait::async_trait]
-impl<FN, I, S, E, T, M> Factory<S, E, T, M> for FN
-where
-    S: 'static,
-    E: 'static,
-    I: Future<Output = Result<S, E>> + Send + 'static,
-    T: FromRequest + Send + 'static,
-    FN: Fn(T, M) -> I + Sync,
-    M: Metadata,
-{
-    async fn call(&self, param: T, meta: M) -> Result<S, E> {
-        (self)(param, meta).await
-    }
+/// Implements `Factory` for handlers taking the `FromRequest` items `$T` followed by the metadata,
+/// extracting them together as a tuple
+macro_rules! factory_tuple {
+    ($($T:ident),*) => {
+        #[async_trait::async_trait]
+        impl<FN, I, S, E, M, $($T),*> Factory<S, E, ($($T,)*), M> for FN
+        where
+            S: 'static,
+            E: 'static,
+            I: Future<Output = Result<S, E>> + Send + 'static,
+            ($($T,)*): FromRequest<M> + Send + 'static,
+            FN: Fn($($T,)* M) -> I + Sync,
+            M: Metadata,
+        {
+            #[allow(non_snake_case)]
+            async fn call(&self, param: ($($T,)*), meta: M) -> Result<S, E> {
+                let ($($T,)*) = param;
+                (self)($($T,)* meta).await
+            }
+        }
+    };
 }
 
+factory_tuple!();
+factory_tuple!(T1);
+factory_tuple!(T1, T2);
+factory_tuple!(T1, T2, T3);
+factory_tuple!(T1, T2, T3, T4);
+factory_tuple!(T1, T2, T3, T4, T5);
+
 impl<F, S, E, T, M> From<Handler<F, S, E, T, M>> for BoxedHandler<M>
 where
     F: Factory<S, E, T, M> + 'static + Send + Sync,
@@ -53,7 +69,7 @@ where
     Error: From<E>,
     E: 'static,
     M: Metadata,
-    T: FromRequest + 'static + Send,
+    T: FromRequest<M> + 'static + Send,
 {
     fn from(t: Handler<F, S, E, T, M>) -> BoxedHandler<M> {
         let hnd = Arc::new(t.hnd);
@@ -61,7 +77,7 @@ where
             let hnd = Arc::clone(&hnd);
             Box::pin(async move {
                 let out = {
-                    let param = T::from_request(&req).await?;
+                    let param = T::from_request(&req, metadata.clone()).await?;
 
                     hnd.call(param, metadata).await?
                 };
@@ -70,12 +86,14 @@ where
                 as std::pin::Pin<Box<dyn Future<Output = Result<BoxedSerialize, Error>> + Send>>
         };
 
-        BoxedHandler(Box::new(inner))
+        BoxedHandler(Arc::new(inner))
     }
 }
 
-type HandlerResult = std::pin::Pin<Box<dyn Future<Output = Result<BoxedSerialize, Error>> + Send>>;
+pub(crate) type HandlerResult =
+    std::pin::Pin<Box<dyn Future<Output = Result<BoxedSerialize, Error>> + Send>>;
 
+#[derive(Clone)]
 pub struct BoxedHandler<M: Metadata>(
-    pub(crate) Box<dyn Fn(RequestObject, M) -> HandlerResult + Send + Sync>,
+    pub(crate) Arc<dyn Fn(RequestObject, M) -> HandlerResult + Send + Sync>,
 );
@@ -1,18 +1,30 @@
 /*!
 A very small and very fast JSON-RPC 2.0 server-focused framework.
 
-Provides integrations for both `hyper` and `actix-web` (both 1.x and 2.x).
-Enable features `actix-web-v1-integration`, `actix-web-v2-integration`, or `hyper-integration` depending on need.
+Provides integrations for both `hyper` and `actix-web` 2.x.
+Enable features `actix-web-v2-integration` or `hyper-integration` depending on need.
 
-`actix-web-v2-integration` is enabled by default. Make sure to add `default-features = false` if using `hyper` or `actix-web` 1.x.
+`actix-web-v2-integration` is enabled by default. Make sure to add `default-features = false` if using `hyper`.
+
+Connection-oriented transports built on `tokio` live in the `transport` module: enable `tcp-transport` to serve newline-delimited JSON over TCP,
+`stdio-transport` to serve stdin/stdout with Language Server Protocol style `Content-Length` framing,
+`unix-transport` to serve a Unix domain socket with the same framing options as TCP,
+or `ws-transport` to serve WebSocket connections.
 
 Also see the `easy-errors` feature flag (not enabled by default). Enabling this flag will implement [`ErrorLike`](https://docs.rs/jsonrpc-v2/&#42;/jsonrpc_v2/trait.ErrorLike.html)
 for anything that implements `Display`, and the display value will be provided in the `message` field of the JSON-RPC 2.0 `Error` response.
 
 Otherwise, custom errors should implement [`ErrorLike`](https://docs.rs/jsonrpc-v2/&#42;/jsonrpc_v2/trait.ErrorLike.html) to map errors to the JSON-RPC 2.0 `Error` response.
 
+A typed client is provided in the `client` module: it allocates request ids, matches responses to requests,
+and deserializes results, over any type implementing its `Transport` trait. Several calls can be sent as one batch,
+with each call resolving to its own typed result. `LoopbackTransport` sends requests straight to a `Server` in the same
+process, which is handy for tests.
+
 Individual method handlers are `async` functions that can take various kinds of args (things that can be extracted from the request, like
-the `Params` or `Data`), and should return a `Result<Item, Error>` where the `Item` is serializable. See examples below.
+the `Params` or `Data`, the request `Id`, the `Method` name, the `RawParams`, or the `Meta`data), and should return a
+`Result<Item, Error>` where the `Item` is serializable. Custom extractors implement `FromRequest`, which also sees the metadata.
+See examples below.
 
 # Usage
 
@@ -22,22 +34,27 @@ use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use serde_json::value::RawValue;
 
+pub mod cancel;
+pub mod client;
 pub mod documentation;
 pub mod error;
 pub mod handler;
+mod limits;
 pub mod middleware;
 pub mod notification;
+pub mod pubsub;
 pub mod request;
 pub mod response;
 pub mod router;
 pub mod server;
+#[cfg(feature = "tokio")]
+pub mod transport;
 
 pub use error::{Error, ErrorLike};
 pub use notification::NotificationBuilder;
-pub use request::{DummyReq, Params};
-use serde::export::Formatter;
+pub use request::{Data, DummyReq, Meta, Method, Params, RawParams};
 pub use server::{Metadata, Server};
-
+use std::fmt::Formatter;
 
 pub type BoxedSerialize = Box<dyn erased_serde::Serialize + Send>;
 
@@ -70,29 +87,128 @@ impl<'de> Deserialize<'de> for V2 {
 
 /// Container for the request ID, which can be a string, number, or null.
 /// Not typically used directly.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Numbers that are not exactly representable as an `i64` (e.g. above `i64::MAX`, fractional,
+/// or written with an exponent) are kept as `Raw`, so they are echoed back exactly as the client sent them.
+#[derive(Debug, Clone, Serialize, Default)]
 #[serde(untagged)]
 pub enum Id {
     Num(i64),
     Str(Box<str>),
+    Raw(Box<RawValue>),
+    #[default]
     Null,
 }
 
 impl Id {
     pub fn is_null(&self) -> bool {
-        match self {
-            Id::Null => true,
+        matches!(self, Id::Null)
+    }
+
+    /// Parse an id from its JSON text, keeping numbers that are not plain `i64`s exactly as written
+    pub(crate) fn from_raw(raw: Box<RawValue>) -> Result<Self, serde_json::Error> {
+        let text = raw.get();
+        match text.as_bytes().first() {
+            Some(b'"') => Ok(Id::Str(serde_json::from_str::<String>(text)?.into_boxed_str())),
+            Some(b'-') | Some(b'0'..=b'9') => match text.parse::<i64>() {
+                Ok(num) if num.to_string() == text => Ok(Id::Num(num)),
+                _ => Ok(Id::Raw(raw)),
+            },
+            _ if text == "null" => Ok(Id::Null),
+            _ => Err(serde::de::Error::custom("id must be a string, a number or null")),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Id {
+    fn deserialize<D>(deserializer: D) -> Result<Id, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(IdVisitor)
+    }
+}
+
+/// Builds an `Id` from any self-describing format, including buffered content like `serde_json::Value`
+///
+/// Numbers that are not `i64`s are kept as their canonical JSON text; only the server's own parsing of
+/// request bytes keeps them exactly as written.
+struct IdVisitor;
+
+impl<'de> serde::de::Visitor<'de> for IdVisitor {
+    type Value = Id;
+
+    fn expecting(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "a string, a number or null")
+    }
+
+    fn visit_i64<E: serde::de::Error>(self, v: i64) -> Result<Id, E> {
+        Ok(Id::Num(v))
+    }
+
+    fn visit_u64<E: serde::de::Error>(self, v: u64) -> Result<Id, E> {
+        Ok(Id::from(v))
+    }
+
+    fn visit_f64<E: serde::de::Error>(self, v: f64) -> Result<Id, E> {
+        let num = serde_json::Number::from_f64(v).ok_or_else(|| E::custom("id must be finite"))?;
+        RawValue::from_string(num.to_string()).map(Id::Raw).map_err(E::custom)
+    }
+
+    fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Id, E> {
+        Ok(Id::Str(v.into()))
+    }
+
+    fn visit_string<E: serde::de::Error>(self, v: String) -> Result<Id, E> {
+        Ok(Id::Str(v.into_boxed_str()))
+    }
+
+    fn visit_unit<E: serde::de::Error>(self) -> Result<Id, E> {
+        Ok(Id::Null)
+    }
+
+    fn visit_none<E: serde::de::Error>(self) -> Result<Id, E> {
+        Ok(Id::Null)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Id, D::Error> {
+        Id::deserialize(deserializer)
+    }
+}
+
+impl PartialEq for Id {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Id::Num(a), Id::Num(b)) => a == b,
+            (Id::Str(a), Id::Str(b)) => a == b,
+            (Id::Raw(a), Id::Raw(b)) => a.get() == b.get(),
+            (Id::Null, Id::Null) => true,
             _ => false,
         }
     }
 }
 
+impl Eq for Id {}
+
+impl std::hash::Hash for Id {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Id::Num(num) => num.hash(state),
+            Id::Str(s) => s.hash(state),
+            Id::Raw(raw) => raw.get().hash(state),
+            Id::Null => {}
+        }
+    }
+}
+
 impl std::fmt::Display for Id {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             Id::Null => write!(f, "null"),
             Id::Num(res) => write!(f, "{}", res),
             Id::Str(res) => write!(f, "{}", res),
+            Id::Raw(res) => write!(f, "{}", res.get()),
         }
     }
 }
@@ -103,6 +219,19 @@ impl From<i64> for Id {
     }
 }
 
+impl From<u64> for Id {
+    fn from(t: u64) -> Self {
+        use std::convert::TryFrom;
+
+        match i64::try_from(t) {
+            Ok(num) => Id::Num(num),
+            Err(_) => {
+                Id::Raw(RawValue::from_string(t.to_string()).expect("integers are valid JSON"))
+            }
+        }
+    }
+}
+
 impl<'a> From<&'a str> for Id {
     fn from(t: &'a str) -> Self {
         Id::Str(t.into())
@@ -115,12 +244,6 @@ impl From<String> for Id {
     }
 }
 
-impl Default for Id {
-    fn default() -> Self {
-        Id::Null
-    }
-}
-
 #[derive(Debug)]
 enum OneOrManyRawValues<'a> {
     Many(Vec<&'a RawValue>),
@@ -0,0 +1,86 @@
+use crate::error::Error;
+
+/// Limits applied to incoming requests before any handler runs
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Limits {
+    pub(crate) max_payload_bytes: Option<usize>,
+    pub(crate) max_batch_items: Option<usize>,
+    pub(crate) max_depth: Option<usize>,
+}
+
+impl Limits {
+    /// Whether a payload of `len` bytes is over the limit, so readers can stop buffering early
+    #[cfg(any(feature = "actix-web-v2-integration", feature = "hyper-integration"))]
+    pub(crate) fn payload_exceeded(&self, len: usize) -> bool {
+        self.max_payload_bytes.is_some_and(|max| len > max)
+    }
+
+    /// Check the size and nesting depth of a raw payload
+    pub(crate) fn check_payload(&self, slice: &[u8]) -> Result<(), Error> {
+        if let Some(max) = self.max_payload_bytes {
+            if slice.len() > max {
+                return Err(Error::invalid_request(format!(
+                    "payload exceeds the limit of {} bytes",
+                    max
+                )));
+            }
+        }
+
+        if let Some(max) = self.max_depth {
+            if depth_exceeds(slice, max) {
+                return Err(Error::invalid_request(format!(
+                    "JSON nesting exceeds the depth limit of {}",
+                    max
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check the number of items in a batch
+    pub(crate) fn check_batch(&self, len: usize) -> Result<(), Error> {
+        match self.max_batch_items {
+            Some(max) if len > max => Err(Error::invalid_request(format!(
+                "batch of {} items exceeds the limit of {} items",
+                len, max
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Whether arrays and objects in `slice` are nested deeper than `max`, ignoring brackets inside strings
+///
+/// A batch array counts as one level, as does the request object inside it.
+fn depth_exceeds(slice: &[u8], max: usize) -> bool {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for &b in slice {
+        if in_string {
+            match b {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match b {
+            b'"' => in_string = true,
+            b'[' | b'{' => {
+                depth += 1;
+                if depth > max {
+                    return true;
+                }
+            }
+            b']' | b'}' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+
+    false
+}
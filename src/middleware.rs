@@ -29,7 +29,7 @@ impl<M: Metadata> Next<'_, '_, M> {
             self.next_middleware = next;
             current.handle(req, metadata, self).await
         } else {
-            (&self.endpoint.0)(req, metadata).await
+            (self.endpoint.0)(req, metadata).await
         }
     }
 }
@@ -31,6 +31,7 @@ impl NotificationBuilder<String> {
             method: method.into_boxed_str(),
             params: params.map(InnerParams::Value),
             id: Id::Null,
+            extensions: Default::default(),
         }
     }
 }
@@ -0,0 +1,353 @@
+//! Server-to-client notifications for subscriptions on stateful transports
+
+use crate::cancel::{CancelHandle, CancellationToken};
+use crate::error::Error;
+use crate::handler::{BoxedHandler, HandlerResult};
+use crate::request::{FromRequest, Params, RequestObject};
+use crate::server::Metadata;
+use crate::{BoxedSerialize, Id, V2};
+
+use futures::channel::{mpsc, oneshot};
+use futures::future::{self, BoxFuture, Future, FutureExt, Shared};
+use futures::ready;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::task::Poll;
+
+/// Identifier of a subscription, unique within a connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SubscriptionId(u64);
+
+impl std::fmt::Display for SubscriptionId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Per-connection state of a stateful transport
+///
+/// Made available to handlers through the request extensions.
+#[derive(Clone)]
+pub(crate) struct Session {
+    inner: Arc<SessionInner>,
+    /// Work to start once the response to the current frame has been queued
+    deferred: Arc<Mutex<Vec<BoxFuture<'static, ()>>>>,
+}
+
+struct SessionInner {
+    sender: mpsc::Sender<Vec<u8>>,
+    next_id: AtomicU64,
+    subscriptions: Mutex<HashMap<SubscriptionId, Subscribed>>,
+    next_key: AtomicU64,
+    in_flight: Mutex<HashMap<Id, (u64, CancelHandle)>>,
+}
+
+/// A live subscription, as the session keeps it until unsubscribed or closed
+struct Subscribed {
+    /// Dropped to resolve [`Sink::closed`](struct.Sink.html#method.closed)
+    _closed: oneshot::Sender<()>,
+    /// Cloned from the session's once, so a subscription holds a single slot in the outgoing buffer
+    sender: mpsc::Sender<Vec<u8>>,
+}
+
+impl Session {
+    #[cfg_attr(not(feature = "tokio"), allow(dead_code))]
+    pub(crate) fn new(sender: mpsc::Sender<Vec<u8>>) -> Self {
+        Session {
+            inner: Arc::new(SessionInner {
+                sender,
+                next_id: AtomicU64::new(0),
+                subscriptions: Mutex::default(),
+                next_key: AtomicU64::new(0),
+                in_flight: Mutex::default(),
+            }),
+            deferred: Arc::default(),
+        }
+    }
+
+    /// The same session, with its own list of deferred work for a newly read frame
+    #[cfg_attr(not(feature = "tokio"), allow(dead_code))]
+    pub(crate) fn for_frame(&self) -> Self {
+        Session { inner: Arc::clone(&self.inner), deferred: Arc::default() }
+    }
+
+    /// Take the work deferred while handling this frame, to be started now that its response is queued
+    #[cfg_attr(not(feature = "tokio"), allow(dead_code))]
+    pub(crate) fn take_deferred(&self) -> Vec<BoxFuture<'static, ()>> {
+        std::mem::take(&mut *self.deferred.lock().expect("deferred lock poisoned"))
+    }
+
+    fn defer(&self, work: BoxFuture<'static, ()>) {
+        self.deferred.lock().expect("deferred lock poisoned").push(work);
+    }
+
+    pub(crate) fn subscribe<N>(&self, method: Arc<str>) -> Sink<N> {
+        let id = SubscriptionId(self.inner.next_id.fetch_add(1, Ordering::Relaxed));
+        let (tx, rx) = oneshot::channel();
+        let subscribed = Subscribed { _closed: tx, sender: self.inner.sender.clone() };
+        self.inner
+            .subscriptions
+            .lock()
+            .expect("subscriptions lock poisoned")
+            .insert(id, subscribed);
+
+        let subscription = Arc::new(Subscription {
+            id,
+            session: Arc::downgrade(&self.inner),
+            sending: async_lock::Mutex::new(()),
+        });
+        Sink { method, subscription, closed: rx.shared(), _n: PhantomData }
+    }
+
+    /// Remove a subscription, returning whether it existed
+    pub(crate) fn unsubscribe(&self, id: SubscriptionId) -> bool {
+        self.inner.unsubscribe(id)
+    }
+
+    /// End every subscription, once the connection will not be read from again
+    #[cfg_attr(not(feature = "tokio"), allow(dead_code))]
+    pub(crate) fn close(&self) {
+        self.inner.subscriptions.lock().expect("subscriptions lock poisoned").clear();
+    }
+
+    /// Register an in-flight request so it can be cancelled by id until the returned guard is dropped
+    ///
+    /// A later request reusing the same id replaces the earlier one.
+    pub(crate) fn track(&self, id: Id) -> (InFlight, CancellationToken) {
+        let key = self.inner.next_key.fetch_add(1, Ordering::Relaxed);
+        let (handle, token) = CancelHandle::new();
+        self.inner
+            .in_flight
+            .lock()
+            .expect("in-flight lock poisoned")
+            .insert(id.clone(), (key, handle));
+
+        (InFlight { session: self.clone(), id, key }, token)
+    }
+
+    /// Cancel the in-flight request with `id`, returning whether there was one
+    pub(crate) fn cancel(&self, id: &Id) -> bool {
+        match self.inner.in_flight.lock().expect("in-flight lock poisoned").remove(id) {
+            Some((_, handle)) => {
+                handle.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Guard keeping a request registered with its [`Session`](struct.Session.html) while it runs
+pub(crate) struct InFlight {
+    session: Session,
+    id: Id,
+    key: u64,
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        let mut in_flight = self.session.inner.in_flight.lock().expect("in-flight lock poisoned");
+        if matches!(in_flight.get(&self.id), Some((key, _)) if *key == self.key) {
+            in_flight.remove(&self.id);
+        }
+    }
+}
+
+impl SessionInner {
+    fn unsubscribe(&self, id: SubscriptionId) -> bool {
+        self.subscriptions.lock().expect("subscriptions lock poisoned").remove(&id).is_some()
+    }
+}
+
+/// Ends a subscription once every [`Sink`](struct.Sink.html) for it has been dropped
+struct Subscription {
+    id: SubscriptionId,
+    session: Weak<SessionInner>,
+    /// Held while a notification waits for room, as the shared sender only wakes one waiting task
+    sending: async_lock::Mutex<()>,
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        if let Some(session) = self.session.upgrade() {
+            session.unsubscribe(self.id);
+        }
+    }
+}
+
+impl Session {
+    fn from_request(req: &RequestObject) -> Result<Self, Error> {
+        req.extensions.get::<Session>().cloned().ok_or(Error::SUBSCRIPTIONS_UNSUPPORTED)
+    }
+}
+
+/// Handler for the subscribe method: extracts `T`, registers a subscription, and responds with its id
+///
+/// `handler` receives the subscription's [`Sink`](struct.Sink.html) only once that response has been
+/// queued, so the id always reaches the client before any notification.
+pub(crate) fn subscribe_handler<M, N, E, T, F, I>(method: Arc<str>, handler: F) -> BoxedHandler<M>
+where
+    M: Metadata,
+    N: 'static,
+    Error: From<E>,
+    T: FromRequest<M> + Send + 'static,
+    F: Fn(T, M, Sink<N>) -> I + Send + Sync + 'static,
+    I: Future<Output = Result<(), E>> + Send + 'static,
+{
+    let handler = Arc::new(handler);
+
+    BoxedHandler(Arc::new(move |req: RequestObject, metadata: M| {
+        let handler = Arc::clone(&handler);
+        let method = Arc::clone(&method);
+        Box::pin(async move {
+            let session = Session::from_request(&req)?;
+            let param = T::from_request(&req, metadata.clone()).await?;
+            let sink = session.subscribe(method);
+            let id = sink.id();
+            let subscription = Arc::clone(&sink.subscription);
+
+            let work = handler(param, metadata, sink);
+            session.defer(Box::pin(async move {
+                if let Err(e) = work.await {
+                    log::debug!("subscription {} ended with error: {:?}", id, Error::from(e));
+                    if let Some(session) = subscription.session.upgrade() {
+                        session.unsubscribe(id);
+                    }
+                }
+            }));
+
+            Ok(Box::new(id) as BoxedSerialize)
+        }) as HandlerResult
+    }))
+}
+
+/// Handler for the unsubscribe method: takes the subscription id as the only positional param,
+/// responding with whether the subscription existed
+pub(crate) fn unsubscribe_handler<M>() -> BoxedHandler<M>
+where
+    M: Metadata,
+{
+    BoxedHandler(Arc::new(move |req: RequestObject, metadata: M| {
+        Box::pin(async move {
+            let session = Session::from_request(&req)?;
+            let Params((id,)) = Params::<(SubscriptionId,)>::from_request(&req, metadata).await?;
+            Ok(Box::new(session.unsubscribe(id)) as BoxedSerialize)
+        }) as HandlerResult
+    }))
+}
+
+/// Error returned when a notification could not be sent through a [`Sink`](struct.Sink.html)
+#[derive(Debug)]
+pub enum NotifyError {
+    /// The client has unsubscribed or the connection has closed
+    Closed,
+    /// The notification could not be serialized
+    Serialize(serde_json::Error),
+}
+
+impl std::fmt::Display for NotifyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NotifyError::Closed => write!(f, "subscription closed"),
+            NotifyError::Serialize(e) => write!(f, "failed to serialize notification: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for NotifyError {}
+
+#[derive(Serialize)]
+struct SubscriptionNotification<'a, N> {
+    jsonrpc: V2,
+    method: &'a str,
+    params: SubscriptionParams<'a, N>,
+}
+
+#[derive(Serialize)]
+struct SubscriptionParams<'a, N> {
+    subscription: SubscriptionId,
+    result: &'a N,
+}
+
+/// Handle used to emit notifications of type `N` for a single subscription
+///
+/// Notifications are sent as `{"jsonrpc": "2.0", "method": <notification>, "params": {"subscription": <id>, "result": <N>}}`.
+/// The subscription ends when the client unsubscribes, the connection closes, the handler returns an
+/// error, or every clone of its `Sink` has been dropped.
+pub struct Sink<N> {
+    method: Arc<str>,
+    subscription: Arc<Subscription>,
+    closed: Shared<oneshot::Receiver<()>>,
+    _n: PhantomData<fn(N)>,
+}
+
+impl<N> Clone for Sink<N> {
+    fn clone(&self) -> Self {
+        Sink {
+            method: Arc::clone(&self.method),
+            subscription: Arc::clone(&self.subscription),
+            closed: self.closed.clone(),
+            _n: PhantomData,
+        }
+    }
+}
+
+impl<N> Sink<N> {
+    /// The id returned to the client for this subscription
+    pub fn id(&self) -> SubscriptionId {
+        self.subscription.id
+    }
+
+    /// Whether the client has unsubscribed or the connection has closed
+    pub fn is_closed(&self) -> bool {
+        match self.subscription.session.upgrade() {
+            Some(session) => !session
+                .subscriptions
+                .lock()
+                .expect("subscriptions lock poisoned")
+                .contains_key(&self.subscription.id),
+            None => true,
+        }
+    }
+
+    /// Resolves once the client has unsubscribed or the connection has closed
+    pub async fn closed(&self) {
+        let _ = self.closed.clone().await;
+    }
+}
+
+impl<N> Sink<N>
+where
+    N: Serialize,
+{
+    /// Send a notification to the client
+    ///
+    /// Waits while the connection's outgoing buffer is full.
+    pub async fn notify(&self, result: N) -> Result<(), NotifyError> {
+        let json = serde_json::to_vec(&SubscriptionNotification {
+            jsonrpc: V2,
+            method: &self.method,
+            params: SubscriptionParams { subscription: self.id(), result: &result },
+        })
+        .map_err(NotifyError::Serialize)?;
+
+        let _sending = self.subscription.sending.lock().await;
+        let mut json = Some(json);
+        future::poll_fn(|cx| {
+            let session = self.subscription.session.upgrade().ok_or(NotifyError::Closed)?;
+            let mut subscriptions =
+                session.subscriptions.lock().expect("subscriptions lock poisoned");
+            let subscribed =
+                subscriptions.get_mut(&self.subscription.id).ok_or(NotifyError::Closed)?;
+
+            ready!(subscribed.sender.poll_ready(cx)).map_err(|_| NotifyError::Closed)?;
+            let json = json.take().expect("notification polled after it was sent");
+            Poll::Ready(subscribed.sender.start_send(json).map_err(|_| NotifyError::Closed))
+        })
+        .await
+    }
+}
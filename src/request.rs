@@ -1,7 +1,11 @@
 use crate::error::Error;
 use crate::notification::NotificationBuilder;
+use crate::server::Metadata;
 use crate::{Id, V2};
-use erased_serde::private::serde::de::DeserializeOwned;
+use extensions::concurrent::Extensions;
+use paperclip::v2::models::DefaultSchemaRaw;
+use paperclip::v2::schema::Apiv2Schema;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::value::RawValue;
 use serde_json::Value;
@@ -12,7 +16,7 @@ use bytes::Bytes;
 
 #[cfg(feature = "bytes-v04")]
 use bytes_v04::Bytes;
-use serde::export::Formatter;
+use std::fmt::Formatter;
 
 /// Builder struct for a request object
 #[derive(Default)]
@@ -49,6 +53,7 @@ impl RequestBuilder<String> {
             method: method.into_boxed_str(),
             params: params.map(InnerParams::Value),
             id,
+            extensions: Extensions::default(),
         }
     }
 }
@@ -63,14 +68,89 @@ impl RequestBuilder<String> {
 pub struct Params<T>(pub T);
 
 /// A trait to extract data from the request
+///
+/// Extractors see the request object, including its [`extensions`](struct.RequestObject.html#method.extensions),
+/// and a clone of the connection's `Metadata`, so they can be built on top of either.
+///
+/// Implemented for tuples of up to 5 items, which are extracted in order, each with its own clone of the metadata.
+///
+/// Handlers registered with [`ServerBuilder::with_method`](../server/struct.ServerBuilder.html#method.with_method)
+/// also need their extractors to implement [`ParamsSchema`](trait.ParamsSchema.html), which describes the params
+/// for the generated documentation. Extractors that do not read the params only need an empty impl:
+///
+/// ```
+/// use jsonrpc_v2::request::{FromRequest, ParamsSchema, RequestObject};
+/// use jsonrpc_v2::Error;
+///
+/// struct MethodLength(usize);
+///
+/// #[async_trait::async_trait]
+/// impl FromRequest for MethodLength {
+///     async fn from_request(req: &RequestObject, _: ()) -> Result<Self, Error> {
+///         Ok(MethodLength(req.method.len()))
+///     }
+/// }
+///
+/// impl ParamsSchema for MethodLength {}
+/// ```
+#[async_trait::async_trait]
+pub trait FromRequest<M: Metadata = ()>: Sized {
+    async fn from_request(req: &RequestObject, metadata: M) -> Result<Self, Error>;
+}
+
+/// Describes the params read by a [`FromRequest`](trait.FromRequest.html) item, for the generated documentation
+pub trait ParamsSchema {
+    /// The schema of the params, or `None` if the item does not read them
+    fn params_schema() -> Option<DefaultSchemaRaw> {
+        None
+    }
+}
+
+impl<T: Apiv2Schema> ParamsSchema for Params<T> {
+    fn params_schema() -> Option<DefaultSchemaRaw> {
+        Some(<Params<T> as Apiv2Schema>::raw_schema())
+    }
+}
+
 #[async_trait::async_trait]
-pub trait FromRequest: Sized {
-    async fn from_request(req: &RequestObject) -> Result<Self, Error>;
+impl<M: Metadata> FromRequest<M> for () {
+    async fn from_request(_: &RequestObject, _: M) -> Result<Self, Error> {
+        Ok(())
+    }
+}
+
+impl ParamsSchema for () {}
+
+macro_rules! tuple_from_request {
+    ($($T:ident),+) => {
+        #[async_trait::async_trait]
+        impl<M, $($T),+> FromRequest<M> for ($($T,)+)
+        where
+            M: Metadata,
+            $($T: FromRequest<M> + Send,)+
+        {
+            async fn from_request(req: &RequestObject, metadata: M) -> Result<Self, Error> {
+                Ok(($($T::from_request(req, metadata.clone()).await?,)+))
+            }
+        }
+
+        impl<$($T: ParamsSchema),+> ParamsSchema for ($($T,)+) {
+            fn params_schema() -> Option<DefaultSchemaRaw> {
+                None$(.or_else($T::params_schema))+
+            }
+        }
+    };
 }
 
+tuple_from_request!(A);
+tuple_from_request!(A, B);
+tuple_from_request!(A, B, C);
+tuple_from_request!(A, B, C, D);
+tuple_from_request!(A, B, C, D, E);
+
 #[async_trait::async_trait]
-impl<T: DeserializeOwned> FromRequest for Params<T> {
-    async fn from_request(req: &RequestObject) -> Result<Self, Error> {
+impl<T: DeserializeOwned, M: Metadata> FromRequest<M> for Params<T> {
+    async fn from_request(req: &RequestObject, _: M) -> Result<Self, Error> {
         let res = match req.params {
             Some(InnerParams::Raw(ref value)) => serde_json::from_str(value.get()),
             Some(InnerParams::Value(ref value)) => serde_json::from_value(value.clone()),
@@ -82,6 +162,9 @@ impl<T: DeserializeOwned> FromRequest for Params<T> {
 }
 
 /// Data/state storage container
+///
+/// Register app state with [`ServerBuilder::with_data`](../server/struct.ServerBuilder.html#method.with_data),
+/// then take `Data<T>` as a [`FromRequest`](trait.FromRequest.html) item in handlers.
 pub struct Data<T>(pub Arc<T>);
 
 impl<T> Data<T> {
@@ -90,11 +173,126 @@ impl<T> Data<T> {
     }
 }
 
+impl<T> Clone for Data<T> {
+    fn clone(&self) -> Self {
+        Data(Arc::clone(&self.0))
+    }
+}
+
+impl<T> From<Arc<T>> for Data<T> {
+    fn from(t: Arc<T>) -> Self {
+        Data(t)
+    }
+}
+
+/// App data registered on the server, shared with every request through its extensions
+#[derive(Clone, Default)]
+pub(crate) struct AppData(pub(crate) Arc<Extensions>);
+
+#[async_trait::async_trait]
+impl<T: Send + Sync + 'static, M: Metadata> FromRequest<M> for Data<T> {
+    async fn from_request(req: &RequestObject, _: M) -> Result<Self, Error> {
+        req.extensions
+            .get::<AppData>()
+            .and_then(|data| data.0.get::<Data<T>>())
+            .cloned()
+            .ok_or_else(|| {
+                Error::internal(format!(
+                    "Data<{}> was not registered with `ServerBuilder::with_data`",
+                    std::any::type_name::<T>()
+                ))
+            })
+    }
+}
+
+impl<T> ParamsSchema for Data<T> {}
+
 impl<T> std::ops::Deref for Data<T> {
     type Target = T;
 
     fn deref(&self) -> &Self::Target {
-        &*self.0
+        &self.0
+    }
+}
+
+/// [`FromRequest`](trait.FromRequest.html) item for the id of the request, `Id::Null` for notifications
+#[async_trait::async_trait]
+impl<M: Metadata> FromRequest<M> for Id {
+    async fn from_request(req: &RequestObject, _: M) -> Result<Self, Error> {
+        Ok(req.id.clone())
+    }
+}
+
+impl ParamsSchema for Id {}
+
+/// [`FromRequest`](trait.FromRequest.html) wrapper for the name of the called method
+#[derive(Debug, Clone)]
+pub struct Method(pub Box<str>);
+
+#[async_trait::async_trait]
+impl<M: Metadata> FromRequest<M> for Method {
+    async fn from_request(req: &RequestObject, _: M) -> Result<Self, Error> {
+        Ok(Method(req.method.clone()))
+    }
+}
+
+impl ParamsSchema for Method {}
+
+impl std::ops::Deref for Method {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// [`FromRequest`](trait.FromRequest.html) wrapper for the params as raw JSON, without deserializing them
+///
+/// Missing params are read as `null`, as with [`Params`](struct.Params.html).
+#[derive(Debug)]
+pub struct RawParams(pub Box<RawValue>);
+
+#[async_trait::async_trait]
+impl<M: Metadata> FromRequest<M> for RawParams {
+    async fn from_request(req: &RequestObject, _: M) -> Result<Self, Error> {
+        let raw = match req.params {
+            Some(InnerParams::Raw(ref value)) => Ok(value.clone()),
+            Some(InnerParams::Value(ref value)) => serde_json::value::to_raw_value(value),
+            None => serde_json::value::to_raw_value(&Value::Null),
+        };
+
+        Ok(raw.map(RawParams).map_err(|_| Error::INVALID_PARAMS)?)
+    }
+}
+
+impl ParamsSchema for RawParams {}
+
+impl std::ops::Deref for RawParams {
+    type Target = RawValue;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// [`FromRequest`](trait.FromRequest.html) wrapper for a clone of the connection's `Metadata`
+#[derive(Debug, Clone)]
+pub struct Meta<M>(pub M);
+
+#[async_trait::async_trait]
+impl<M: Metadata> FromRequest<M> for Meta<M> {
+    async fn from_request(_: &RequestObject, metadata: M) -> Result<Self, Error> {
+        Ok(Meta(metadata))
+    }
+}
+
+impl<M> ParamsSchema for Meta<M> {}
+
+impl<M> std::ops::Deref for Meta<M> {
+    type Target = M;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
     }
 }
 
@@ -120,10 +318,13 @@ impl std::fmt::Display for InnerParams {
 pub struct RequestObject {
     pub jsonrpc: V2,
     pub method: Box<str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub params: Option<InnerParams>,
     #[serde(deserialize_with = "RequestObject::deserialize_id")]
     #[serde(skip_serializing_if = "Id::is_null")]
     pub id: Id,
+    #[serde(skip)]
+    pub(crate) extensions: Extensions,
 }
 
 impl std::fmt::Display for RequestObject {
@@ -147,15 +348,90 @@ pub(crate) struct BytesRequestObject {
     pub(crate) jsonrpc: V2,
     pub(crate) method: Box<str>,
     pub(crate) params: Option<Box<RawValue>>,
-    #[serde(deserialize_with = "RequestObject::deserialize_id")]
+    #[serde(deserialize_with = "BytesRequestObject::deserialize_id")]
     #[serde(skip_serializing_if = "Id::is_null")]
     pub(crate) id: Id,
 }
 
+impl BytesRequestObject {
+    /// Best-effort recovery of the `id` of an item that is not a valid request object,
+    /// so the error response can be attributed to the right call
+    pub(crate) fn recover_id(raw: &str) -> Id {
+        #[derive(Deserialize)]
+        struct IdOnly {
+            #[serde(default, deserialize_with = "BytesRequestObject::deserialize_id")]
+            id: Id,
+        }
+
+        serde_json::from_str::<IdOnly>(raw).map(|x| x.id).unwrap_or(Id::Null)
+    }
+
+    /// Parse the id from its JSON text, so it is echoed back exactly as the client sent it
+    fn deserialize_id<'de, D>(deserializer: D) -> Result<Id, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match Option::<Box<RawValue>>::deserialize(deserializer)? {
+            Some(raw) => Id::from_raw(raw).map_err(serde::de::Error::custom),
+            None => Ok(Id::Null),
+        }
+    }
+
+    /// Whether `raw` is a request object following the specification exactly
+    pub(crate) fn is_strictly_valid(raw: &str) -> bool {
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct StrictRequestObject<'a> {
+            #[serde(rename = "jsonrpc")]
+            _jsonrpc: V2,
+            #[serde(rename = "method")]
+            _method: String,
+            #[serde(borrow, default, deserialize_with = "present")]
+            params: Option<&'a RawValue>,
+            #[serde(borrow, default, deserialize_with = "present")]
+            id: Option<&'a RawValue>,
+        }
+
+        // unlike `Option::deserialize`, keeps an explicit `null` as `Some`
+        fn present<'de, D>(deserializer: D) -> Result<Option<&'de RawValue>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            <&RawValue>::deserialize(deserializer).map(Some)
+        }
+
+        let req = match serde_json::from_str::<StrictRequestObject>(raw) {
+            Ok(req) => req,
+            Err(_) => return false,
+        };
+
+        let params_ok = req
+            .params
+            .map_or(true, |params| params.get().starts_with('[') || params.get().starts_with('{'));
+
+        // a `null` id would be taken for a notification and never answered
+        let id_ok = req.id.map_or(true, |id| match id.get().as_bytes().first() {
+            Some(b'"') => true,
+            Some(b'-') | Some(b'0'..=b'9') => {
+                id.get().bytes().all(|b| b == b'-' || b.is_ascii_digit())
+            }
+            _ => false,
+        });
+
+        params_ok && id_ok
+    }
+}
+
 impl From<BytesRequestObject> for RequestObject {
     fn from(t: BytesRequestObject) -> Self {
         let BytesRequestObject { jsonrpc, method, params, id } = t;
-        RequestObject { jsonrpc, method, params: params.map(InnerParams::Raw), id }
+        RequestObject {
+            jsonrpc,
+            method,
+            params: params.map(InnerParams::Raw),
+            id,
+            extensions: Extensions::default(),
+        }
     }
 }
 
@@ -170,6 +446,16 @@ impl RequestObject {
         NotificationBuilder::default()
     }
 
+    /// Per-request extensions, populated by the server and middlewares
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    /// Mutable access to the per-request extensions
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+
     fn deserialize_id<'de, D>(deserializer: D) -> Result<Id, D::Error>
     where
         D: Deserializer<'de>,
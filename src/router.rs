@@ -1,14 +1,39 @@
-use crate::handler::BoxedHandler;
+use crate::error::Error;
+use crate::handler::{BoxedHandler, Factory, Handler};
 use crate::middleware::Middleware;
+use crate::request::FromRequest;
 use crate::server::Metadata;
+use serde::Serialize;
 use std::collections::HashMap;
+use std::hash::{BuildHasherDefault, Hasher};
 use std::sync::Arc;
+use std::time::Duration;
 
+#[derive(Clone)]
 pub struct MapRouter<M: Metadata>(HashMap<String, Route<M>>);
 
+#[derive(Clone)]
 pub struct Route<M: Metadata> {
     pub(crate) handler: BoxedHandler<M>,
     pub(crate) middlewares: Vec<Arc<dyn Middleware<M>>>,
+    pub(crate) timeout: Option<Duration>,
+}
+
+impl<M: Metadata> Route<M> {
+    /// A route to `handler`, without middlewares or a timeout of its own, for filling a [`Router`](trait.Router.html) by hand
+    ///
+    /// Routes registered through [`ServerBuilder`](../server/struct.ServerBuilder.html) are built by the builder instead,
+    /// which adds its middlewares to them.
+    pub fn new<S, E, T, F>(handler: F) -> Self
+    where
+        F: Factory<S, E, T, M> + Send + Sync + 'static,
+        S: Serialize + Send + 'static,
+        Error: From<E>,
+        E: 'static,
+        T: FromRequest<M> + Send + 'static,
+    {
+        Route { handler: Handler::new(handler).into(), middlewares: vec![], timeout: None }
+    }
 }
 
 impl<M: Metadata> Default for MapRouter<M> {
@@ -17,9 +42,31 @@ impl<M: Metadata> Default for MapRouter<M> {
     }
 }
 
+/// The routes of a [`Router`](trait.Router.html) with their names, as listed by `Router::iter`
+pub type Routes<'a, M> = Box<dyn Iterator<Item = (&'a str, &'a Route<M>)> + 'a>;
+
+/// Method lookup used by the server
+///
+/// Any implementation that is also `Clone + Send + Sync` can be passed to
+/// [`Server::with_router`](../server/struct.Server.html#method.with_router).
 pub trait Router<M: Metadata>: Default {
     fn get(&self, name: &str) -> Option<&Route<M>>;
     fn insert(&mut self, name: String, route: Route<M>) -> Option<Route<M>>;
+    /// Remove the route registered under `name`, returning it
+    ///
+    /// Routers that never remove routes can keep the default, which removes nothing, so
+    /// [`Registry::unregister`](../server/struct.Registry.html#method.unregister) always returns `false`.
+    fn remove(&mut self, _name: &str) -> Option<Route<M>> {
+        None
+    }
+    /// Every registered name with its route, as inserted, or `None` if the router cannot list them
+    ///
+    /// Used to copy the routes of a builder mounted with
+    /// [`with_mount`](../server/struct.ServerBuilder.html#method.with_mount), and to check them for overlaps.
+    /// Routers that keep the default cannot take part in `with_mount`, which panics for them.
+    fn iter(&self) -> Option<Routes<'_, M>> {
+        None
+    }
 }
 
 impl<M: Metadata> Router<M> for MapRouter<M> {
@@ -29,4 +76,124 @@ impl<M: Metadata> Router<M> for MapRouter<M> {
     fn insert(&mut self, name: String, route: Route<M>) -> Option<Route<M>> {
         self.0.insert(name, route)
     }
+    fn remove(&mut self, name: &str) -> Option<Route<M>> {
+        self.0.remove(name)
+    }
+    fn iter(&self) -> Option<Routes<'_, M>> {
+        Some(Box::new(self.0.iter().map(|(name, route)| (name.as_str(), route))))
+    }
+}
+
+/// Router with a cheaper hash than `MapRouter`, and wildcard patterns
+///
+/// A name ending in `*`, like `debug_*`, is a pattern matching every method that starts with the rest of it.
+/// Exact names are looked up first, then the pattern with the longest matching prefix.
+/// `cargo bench --bench router` compares lookups with `MapRouter`.
+#[derive(Clone)]
+pub struct FastRouter<M: Metadata> {
+    exact: HashMap<String, Route<M>, BuildHasherDefault<FnvHasher>>,
+    /// Patterns, longest first
+    wildcards: Vec<(String, Route<M>)>,
+}
+
+impl<M: Metadata> Default for FastRouter<M> {
+    fn default() -> Self {
+        FastRouter { exact: HashMap::default(), wildcards: Vec::new() }
+    }
+}
+
+impl<M: Metadata> Router<M> for FastRouter<M> {
+    fn get(&self, name: &str) -> Option<&Route<M>> {
+        self.exact.get(name).or_else(|| {
+            self.wildcards
+                .iter()
+                .find(|(pattern, _)| name.starts_with(&pattern[..pattern.len() - 1]))
+                .map(|(_, route)| route)
+        })
+    }
+    fn insert(&mut self, name: String, route: Route<M>) -> Option<Route<M>> {
+        if !name.ends_with('*') {
+            return self.exact.insert(name, route);
+        }
+
+        match self.wildcards.iter_mut().find(|(pattern, _)| *pattern == name) {
+            Some((_, existing)) => Some(std::mem::replace(existing, route)),
+            None => {
+                self.wildcards.push((name, route));
+                self.wildcards.sort_by_key(|(pattern, _)| std::cmp::Reverse(pattern.len()));
+                None
+            }
+        }
+    }
+    fn remove(&mut self, name: &str) -> Option<Route<M>> {
+        if !name.ends_with('*') {
+            return self.exact.remove(name);
+        }
+
+        let index = self.wildcards.iter().position(|(pattern, _)| pattern == name)?;
+        Some(self.wildcards.remove(index).1)
+    }
+    fn iter(&self) -> Option<Routes<'_, M>> {
+        let exact = self.exact.iter().map(|(name, route)| (name.as_str(), route));
+        let wildcards = self.wildcards.iter().map(|(pattern, route)| (pattern.as_str(), route));
+        Some(Box::new(exact.chain(wildcards)))
+    }
+}
+
+/// FNV-1a, which is much cheaper than the default SipHash for keys as short as method names
+///
+/// It offers no protection against collision flooding, but names from requests are only ever
+/// looked up, never inserted, so clients cannot grow the table.
+struct FnvHasher(u64);
+
+impl Default for FnvHasher {
+    fn default() -> Self {
+        FnvHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for FnvHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Object-safe view of a [`Router`](trait.Router.html), so the server is not generic over it
+pub(crate) trait DynRouter<M: Metadata>: Send + Sync {
+    fn get(&self, name: &str) -> Option<&Route<M>>;
+    fn insert(&mut self, name: String, route: Route<M>) -> Option<Route<M>>;
+    fn remove(&mut self, name: &str) -> Option<Route<M>>;
+    fn iter(&self) -> Option<Routes<'_, M>>;
+    fn boxed_clone(&self) -> BoxedRouter<M>;
+}
+
+pub(crate) type BoxedRouter<M> = Box<dyn DynRouter<M>>;
+
+impl<M, R> DynRouter<M> for R
+where
+    M: Metadata,
+    R: Router<M> + Clone + Send + Sync + 'static,
+{
+    fn get(&self, name: &str) -> Option<&Route<M>> {
+        Router::get(self, name)
+    }
+    fn insert(&mut self, name: String, route: Route<M>) -> Option<Route<M>> {
+        Router::insert(self, name, route)
+    }
+    fn remove(&mut self, name: &str) -> Option<Route<M>> {
+        Router::remove(self, name)
+    }
+    fn iter(&self) -> Option<Routes<'_, M>> {
+        Router::iter(self)
+    }
+    fn boxed_clone(&self) -> BoxedRouter<M> {
+        Box::new(self.clone())
+    }
 }
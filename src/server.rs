@@ -1,17 +1,32 @@
+use crate::cancel::{CancelParams, CANCEL_METHOD};
 use crate::documentation::{DocNotification, DocRoute, SpecHandler};
 use crate::error::Error;
-use crate::handler::{Factory, Handler};
+use crate::handler::{BoxedHandler, Factory, Handler};
+use crate::limits::Limits;
 use crate::middleware::{Middleware, Next};
-use crate::request::{BytesRequestObject, FromRequest, RequestKind, RequestObject};
+use crate::pubsub::{self, Session, Sink};
+use crate::request::{
+    AppData, BytesRequestObject, Data, FromRequest, Params, ParamsSchema, RequestKind,
+    RequestObject,
+};
 use crate::response::{ManyResponseObjects, ResponseObject, ResponseObjects, SingleResponseObject};
-use crate::router::{MapRouter, Route, Router};
-use crate::{Id, OneOrManyRawValues};
+use crate::router::{BoxedRouter, MapRouter, Route, Router};
+use crate::{BoxedSerialize, Id, OneOrManyRawValues};
+use arc_swap::ArcSwap;
+use async_lock::Semaphore;
+use extensions::concurrent::Extensions;
+use futures_timer::Delay;
 use paperclip::v2::schema::Apiv2Schema;
 use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use std::any::TypeId;
+use std::collections::HashSet;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use futures::{
     future::{self, Future, FutureExt},
+    pin_mut,
     stream::StreamExt,
 };
 
@@ -26,9 +41,29 @@ pub struct Server<M>
 where
     M: Metadata,
 {
-    router: MapRouter<M>,
-    #[allow(dead_code)]
+    router: Arc<ArcSwap<BoxedRouter<M>>>,
+    fallback: Option<Route<M>>,
     middlewares: Vec<Arc<dyn Middleware<M>>>,
+    timeout: Option<Duration>,
+    batch_concurrency: BatchConcurrency,
+    concurrency: Option<Semaphore>,
+    ordered_batches: bool,
+    strict: bool,
+    limits: Limits,
+    data: AppData,
+}
+
+/// How many requests of a single batch are handled at the same time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BatchConcurrency {
+    /// Every request in the batch at once
+    #[default]
+    Unbounded,
+    /// At most this many requests at once; a limit of 0 is treated as 1
+    Limit(usize),
+    /// One request at a time, in the order they appear in the batch,
+    /// for batches whose calls depend on each other
+    Sequential,
 }
 
 /// Builder used to add methods to a server
@@ -38,31 +73,80 @@ pub struct ServerBuilder<M>
 where
     M: Metadata,
 {
-    router: MapRouter<M>,
+    router: BoxedRouter<M>,
+    fallback: Option<Route<M>>,
     routes: Vec<DocRoute>,
     notifications: Vec<DocNotification>,
     middlewares: Vec<Arc<dyn Middleware<M>>>,
+    timeout: Option<Duration>,
+    batch_concurrency: BatchConcurrency,
+    max_concurrency: Option<usize>,
+    ordered_batches: bool,
+    strict: bool,
+    limits: Limits,
+    data: Vec<DataEntry>,
+    mounted: HashSet<String>,
+}
+
+/// App data registered on a builder, kept by type until the server is finished so mounted builders can merge theirs
+struct DataEntry {
+    type_id: TypeId,
+    type_name: &'static str,
+    insert: Box<dyn FnOnce(&mut Extensions) + Send + Sync>,
 }
 
 impl<M: Metadata> Server<M> {
+    #[allow(clippy::new_ret_no_self)]
     pub fn new(middlewares: Vec<Arc<dyn Middleware<M>>>) -> ServerBuilder<M> {
         Self::with_router(MapRouter::default(), middlewares)
     }
-    pub fn with_router(
-        router: MapRouter<M>,
-        middlewares: Vec<Arc<dyn Middleware<M>>>,
-    ) -> ServerBuilder<M> {
-        ServerBuilder { router, routes: Vec::default(), notifications: Vec::default(), middlewares }
+
+    /// Start building a server that looks up methods with `router`, e.g. a
+    /// [`FastRouter`](../router/struct.FastRouter.html) for wildcard patterns
+    pub fn with_router<R>(router: R, middlewares: Vec<Arc<dyn Middleware<M>>>) -> ServerBuilder<M>
+    where
+        R: Router<M> + Clone + Send + Sync + 'static,
+    {
+        ServerBuilder {
+            router: Box::new(router),
+            fallback: None,
+            routes: Vec::default(),
+            notifications: Vec::default(),
+            middlewares,
+            timeout: None,
+            batch_concurrency: BatchConcurrency::default(),
+            max_concurrency: None,
+            ordered_batches: false,
+            strict: false,
+            limits: Limits::default(),
+            data: Vec::default(),
+            mounted: HashSet::default(),
+        }
     }
 }
 
 impl<M: Metadata> ServerBuilder<M> {
+    /// Register app state, available to handlers through the [`Data<T>`](../request/struct.Data.html) extractor
+    ///
+    /// Registering data of the same type again replaces it.
+    pub fn with_data<T: Send + Sync + 'static>(mut self, data: Data<T>) -> Self {
+        let type_id = TypeId::of::<Data<T>>();
+        self.data.retain(|entry| entry.type_id != type_id);
+        self.data.push(DataEntry {
+            type_id,
+            type_name: std::any::type_name::<T>(),
+            insert: Box::new(move |extensions| {
+                extensions.insert(data);
+            }),
+        });
+        self
+    }
+
     /// Add a method handler to the server
     ///
     /// The method is an async function that takes up to 5 [`FromRequest`](trait.FromRequest.html) items
-    /// and returns a value that can be resolved to a `TryFuture`, where `TryFuture::Ok` is a serializable object, e.g.:
-    ///
-
+    /// followed by the metadata, and returns a value that can be resolved to a `TryFuture`,
+    /// where `TryFuture::Ok` is a serializable object.
     pub fn with_method<'de, N, S, E, T, F>(self, name: N, handler: F) -> Self
     where
         N: Into<String> + Clone,
@@ -70,16 +154,76 @@ impl<M: Metadata> ServerBuilder<M> {
         S: Serialize + Deserialize<'de> + Send + Apiv2Schema + 'static,
         Error: From<E>,
         E: 'static,
-        T: FromRequest + Deserialize<'de> + Send + Apiv2Schema + 'static,
+        T: FromRequest<M> + ParamsSchema + Send + 'static,
     {
         self.with_method_middleware(name, handler, vec![])
     }
 
     pub fn with_method_middleware<'de, N, S, E, T, F>(
+        self,
+        name: N,
+        handler: F,
+        middlewares: Vec<Arc<dyn Middleware<M>>>,
+    ) -> Self
+    where
+        N: Into<String> + Clone,
+        F: Factory<S, E, T, M> + Send + Sync + 'static,
+        S: Serialize + Deserialize<'de> + Send + Apiv2Schema + 'static,
+        Error: From<E>,
+        E: 'static,
+        T: FromRequest<M> + ParamsSchema + Send + 'static,
+    {
+        self.add_method(name, handler, middlewares, None)
+    }
+
+    /// Add a method handler that is abandoned with a timeout error if it runs longer than `timeout`
+    ///
+    /// Overrides the server-wide default set with [`with_timeout`](#method.with_timeout).
+    pub fn with_method_timeout<'de, N, S, E, T, F>(
+        self,
+        name: N,
+        handler: F,
+        timeout: Duration,
+    ) -> Self
+    where
+        N: Into<String> + Clone,
+        F: Factory<S, E, T, M> + Send + Sync + 'static,
+        S: Serialize + Deserialize<'de> + Send + Apiv2Schema + 'static,
+        Error: From<E>,
+        E: 'static,
+        T: FromRequest<M> + ParamsSchema + Send + 'static,
+    {
+        self.add_method(name, handler, vec![], Some(timeout))
+    }
+
+    /// Add a method handler with its own middlewares and timeout
+    ///
+    /// Combines [`with_method_middleware`](#method.with_method_middleware) and
+    /// [`with_method_timeout`](#method.with_method_timeout); the timeout covers the method's middlewares too.
+    pub fn with_method_middleware_timeout<'de, N, S, E, T, F>(
+        self,
+        name: N,
+        handler: F,
+        middlewares: Vec<Arc<dyn Middleware<M>>>,
+        timeout: Duration,
+    ) -> Self
+    where
+        N: Into<String> + Clone,
+        F: Factory<S, E, T, M> + Send + Sync + 'static,
+        S: Serialize + Deserialize<'de> + Send + Apiv2Schema + 'static,
+        Error: From<E>,
+        E: 'static,
+        T: FromRequest<M> + ParamsSchema + Send + 'static,
+    {
+        self.add_method(name, handler, middlewares, Some(timeout))
+    }
+
+    fn add_method<'de, N, S, E, T, F>(
         mut self,
         name: N,
         handler: F,
         middlewares: Vec<Arc<dyn Middleware<M>>>,
+        timeout: Option<Duration>,
     ) -> Self
     where
         N: Into<String> + Clone,
@@ -87,41 +231,324 @@ impl<M: Metadata> ServerBuilder<M> {
         S: Serialize + Deserialize<'de> + Send + Apiv2Schema + 'static,
         Error: From<E>,
         E: 'static,
-        T: FromRequest + Deserialize<'de> + Send + Apiv2Schema + 'static,
+        T: FromRequest<M> + ParamsSchema + Send + 'static,
     {
         self.routes.push(DocRoute {
             name: name.clone().into(),
-            request: T::raw_schema(),
+            request: T::params_schema().unwrap_or_default(),
             response: S::raw_schema(),
         });
+        self.insert_route(name.into(), Handler::new(handler).into(), middlewares, timeout);
+        self
+    }
+
+    /// Set the handler for methods that are not registered, instead of responding with `METHOD_NOT_FOUND`
+    ///
+    /// The fallback runs through the server middlewares like any other method, and can take the
+    /// [`Method`](../request/struct.Method.html) extractor to see which method was called, e.g. for proxying
+    /// or for a custom error suggesting similar method names in its `data`. It is not listed in the documentation.
+    pub fn with_fallback<S, E, T, F>(mut self, handler: F) -> Self
+    where
+        F: Factory<S, E, T, M> + Send + Sync + 'static,
+        S: Serialize + Send + 'static,
+        Error: From<E>,
+        E: 'static,
+        T: FromRequest<M> + Send + 'static,
+    {
+        self.fallback = Some(Route {
+            handler: Handler::new(handler).into(),
+            middlewares: self.middlewares.clone(),
+            timeout: None,
+        });
+        self
+    }
+
+    /// Set how many requests of a single batch are handled at the same time
+    pub fn with_batch_concurrency(mut self, batch_concurrency: BatchConcurrency) -> Self {
+        self.batch_concurrency = batch_concurrency;
+        self
+    }
+
+    /// Return batch responses in the order of the requests, rather than as they complete
+    ///
+    /// Items that are not valid request objects get their error response at their original position.
+    /// Notifications still get no response, so positions only line up for batches without them.
+    pub fn with_ordered_batches(mut self, ordered: bool) -> Self {
+        self.ordered_batches = ordered;
+        self
+    }
+
+    /// Limit how many handlers run at the same time across all requests to the server
+    ///
+    /// Requests beyond the limit wait for a running handler to finish; a limit of 0 is treated as 1.
+    pub fn with_max_concurrency(mut self, limit: usize) -> Self {
+        self.max_concurrency = Some(limit);
+        self
+    }
+
+    /// Reject request objects that do not follow the JSON-RPC 2.0 specification exactly
+    ///
+    /// By default missing `jsonrpc` and `method` members are tolerated, and `params` may be any JSON value.
+    /// In strict mode, request objects parsed from bytes are answered with `INVALID_REQUEST` if `jsonrpc`
+    /// is not exactly `"2.0"`, `method` is not a string, `params` is present but not an array or object,
+    /// `id` is not a string or an integer, or there are any other members. An explicit `"id": null` is
+    /// rejected too, since only a request without an `id` member is a notification and goes unanswered.
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Reject payloads larger than `limit` bytes with `INVALID_REQUEST`
+    ///
+    /// The HTTP integrations stop reading the body once it exceeds the limit.
+    pub fn with_max_payload_bytes(mut self, limit: usize) -> Self {
+        self.limits.max_payload_bytes = Some(limit);
+        self
+    }
+
+    /// Reject batches of more than `limit` requests with `INVALID_REQUEST`
+    pub fn with_max_batch_items(mut self, limit: usize) -> Self {
+        self.limits.max_batch_items = Some(limit);
+        self
+    }
+
+    /// Reject payloads with arrays and objects nested more than `limit` levels deep with `INVALID_REQUEST`
+    ///
+    /// A request object is one level, and a batch adds another.
+    pub fn with_max_depth(mut self, limit: usize) -> Self {
+        self.limits.max_depth = Some(limit);
+        self
+    }
+
+    /// Set the default deadline for every method
+    ///
+    /// Handlers still running after `timeout` are abandoned and the caller receives
+    /// [`Error::timeout`](enum.Error.html#method.timeout).
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Add a subscription, served over stateful transports by a `subscribe` and an `unsubscribe` method
+    ///
+    /// Calling `subscribe` extracts the [`FromRequest`](trait.FromRequest.html) item and passes it, along
+    /// with a [`Sink`](pubsub/struct.Sink.html) for sending `notification`s, to the handler; the client
+    /// receives the new subscription id before the handler starts, so it may notify right away and run for
+    /// as long as the subscription lasts. `unsubscribe` takes the id as its only positional param. On
+    /// stateless transports both methods respond with `SUBSCRIPTIONS_UNSUPPORTED`.
+    pub fn with_subscription<'de, N, P, E, T, F, I>(
+        mut self,
+        subscribe: N,
+        unsubscribe: N,
+        notification: N,
+        handler: F,
+    ) -> Self
+    where
+        N: Into<String>,
+        P: Serialize + Deserialize<'de> + Apiv2Schema + 'static,
+        Error: From<E>,
+        E: 'static,
+        T: FromRequest<M> + ParamsSchema + Send + 'static,
+        F: Fn(T, M, Sink<P>) -> I + Send + Sync + 'static,
+        I: Future<Output = Result<(), E>> + Send + 'static,
+    {
+        let subscribe = subscribe.into();
+        let unsubscribe = unsubscribe.into();
+        let notification = notification.into();
+
+        self.routes.push(DocRoute {
+            name: subscribe.clone(),
+            request: T::params_schema().unwrap_or_default(),
+            response: u64::raw_schema(),
+        });
+        self.routes.push(DocRoute {
+            name: unsubscribe.clone(),
+            request: Vec::<u64>::raw_schema(),
+            response: bool::raw_schema(),
+        });
+        self.notifications
+            .push(DocNotification { notification: P::raw_schema(), name: notification.clone() });
+
+        let handler = pubsub::subscribe_handler(notification.into(), handler);
+        self.insert_route(subscribe, handler, vec![], None);
+        self.insert_route(unsubscribe, pubsub::unsubscribe_handler(), vec![], None);
+        self
+    }
+
+    /// Mount the methods of another builder under `prefix`, e.g. `"eth_"` or `"admin."`
+    ///
+    /// The mounted methods keep their own middlewares and timeouts, run after this builder's middlewares,
+    /// fall back to the mounted builder's default timeout, and are listed in the generated documentation
+    /// under their prefixed names. Notification names are not prefixed, as subscriptions send them unchanged.
+    /// Data registered on the mounted builder with [`with_data`](#method.with_data) is shared with the whole server.
+    ///
+    /// # Panics
+    ///
+    /// If a prefixed name overlaps a name already registered, or one registered later on this builder.
+    /// Names ending in `*` count as the patterns [`FastRouter`](../router/struct.FastRouter.html) treats
+    /// them as, so mounting `eth_call` under `""` overlaps an existing `eth_*`, and so does mounting
+    /// anything under `"eth_"`.
+    ///
+    /// If data of the same type is registered on both builders, or the mounted builder has a fallback or any of
+    /// the batch, concurrency, strict mode or limit settings, which only apply to the whole server.
+    ///
+    /// If the router of either builder keeps the default [`Router::iter`](../router/trait.Router.html#method.iter),
+    /// so its routes cannot be listed.
+    pub fn with_mount<P: Into<String>>(mut self, prefix: P, builder: ServerBuilder<M>) -> Self {
+        let prefix = prefix.into();
+        let ServerBuilder {
+            router,
+            fallback,
+            routes,
+            notifications,
+            middlewares: _,
+            timeout,
+            batch_concurrency,
+            max_concurrency,
+            ordered_batches,
+            strict,
+            limits,
+            data,
+            mounted: _,
+        } = builder;
+
+        let server_wide = [
+            ("with_fallback", fallback.is_some()),
+            ("with_batch_concurrency", batch_concurrency != BatchConcurrency::default()),
+            ("with_max_concurrency", max_concurrency.is_some()),
+            ("with_ordered_batches", ordered_batches),
+            ("with_strict", strict),
+            ("with_max_payload_bytes", limits.max_payload_bytes.is_some()),
+            ("with_max_batch_items", limits.max_batch_items.is_some()),
+            ("with_max_depth", limits.max_depth.is_some()),
+        ];
+        if let Some((setting, _)) = server_wide.iter().find(|(_, set)| *set) {
+            panic!(
+                "cannot mount under `{}`: `{}` only applies to the whole server",
+                prefix, setting
+            );
+        }
+
+        for entry in data {
+            if self.data.iter().any(|existing| existing.type_id == entry.type_id) {
+                panic!(
+                    "cannot mount under `{}`: `Data<{}>` is registered on both builders",
+                    prefix, entry.type_name
+                );
+            }
+            self.data.push(entry);
+        }
+
+        let existing = match self.router.iter() {
+            Some(routes) => routes.map(|(name, _)| name.to_owned()).collect::<Vec<_>>(),
+            None => panic!(
+                "cannot mount under `{}`: the router of this builder does not implement `Router::iter`",
+                prefix
+            ),
+        };
+        let mounting = router.iter().unwrap_or_else(|| {
+            panic!(
+                "cannot mount under `{}`: the router of the mounted builder does not implement `Router::iter`",
+                prefix
+            )
+        });
+
+        for (name, route) in mounting {
+            let name = format!("{}{}", prefix, name);
+            if let Some(other) = existing.iter().find(|other| overlaps(other, &name)) {
+                panic!("cannot mount `{}`: `{}` is already registered", name, other);
+            }
+
+            let Route { handler, middlewares, timeout: route_timeout } = route.clone();
+            self.insert_route(name.clone(), handler, middlewares, route_timeout.or(timeout));
+            self.mounted.insert(name);
+        }
+
+        self.routes.extend(
+            routes
+                .into_iter()
+                .map(|route| DocRoute { name: format!("{}{}", prefix, route.name), ..route }),
+        );
+        self.notifications.extend(notifications);
+        self
+    }
+
+    fn insert_route(
+        &mut self,
+        name: String,
+        handler: BoxedHandler<M>,
+        middlewares: Vec<Arc<dyn Middleware<M>>>,
+        timeout: Option<Duration>,
+    ) -> Option<Route<M>> {
+        if let Some(mounted) = self.mounted.iter().find(|mounted| overlaps(mounted, &name)) {
+            panic!(
+                "cannot register `{}`: mounted method `{}` is already registered",
+                name, mounted
+            );
+        }
+
         let mut new_middlewares = vec![];
 
         self.middlewares.iter().for_each(|el| new_middlewares.push(el.clone()));
         middlewares.iter().for_each(|el| new_middlewares.push(el.clone()));
 
-        let route = Route { handler: Handler::new(handler).into(), middlewares: new_middlewares };
-        self.router.insert(name.into(), route);
-        self
+        let route = Route { handler, middlewares: new_middlewares, timeout };
+        self.router.insert(name, route)
     }
 
     /// Convert the server builder into the finished struct, wrapped in an `Arc`
     pub fn finish(self) -> Arc<Server<M>> {
         let builder = self.add_documentation_route();
-        Arc::new(Server { router: builder.router, middlewares: builder.middlewares })
+        Arc::new(builder.finish_unwrapped())
     }
 
     fn add_documentation_route(mut self) -> Self {
         let spec_handler =
             SpecHandler { routes: self.routes.clone(), notifications: self.notifications.clone() };
-        let route = Route { handler: Handler::new(spec_handler).into(), middlewares: vec![] };
+        let route = Route {
+            handler: Handler::new(spec_handler).into(),
+            middlewares: vec![],
+            timeout: None,
+        };
         self.router.insert("__docs__".into(), route);
         self
     }
 
     /// Convert the server builder into the finished struct
     pub fn finish_unwrapped(self) -> Server<M> {
-        let ServerBuilder { router, routes: _, notifications: _, middlewares } = self;
-        Server { router, middlewares }
+        let ServerBuilder {
+            router,
+            fallback,
+            routes: _,
+            notifications: _,
+            middlewares,
+            timeout,
+            batch_concurrency,
+            max_concurrency,
+            ordered_batches,
+            strict,
+            limits,
+            data,
+            mounted: _,
+        } = self;
+
+        let mut extensions = Extensions::default();
+        for entry in data {
+            (entry.insert)(&mut extensions);
+        }
+
+        Server {
+            router: Arc::new(ArcSwap::from_pointee(router)),
+            fallback,
+            middlewares,
+            timeout,
+            batch_concurrency,
+            concurrency: max_concurrency.map(|limit| Semaphore::new(limit.max(1))),
+            ordered_batches,
+            strict,
+            limits,
+            data: AppData(Arc::new(extensions)),
+        }
     }
 
     pub fn with_notification<'de, N: Deserialize<'de> + Send + Apiv2Schema + 'static>(
@@ -133,17 +560,166 @@ impl<M: Metadata> ServerBuilder<M> {
     }
 }
 
+/// Handle to register, replace and unregister methods of a running server
+///
+/// Obtained from [`Server::registry`](struct.Server.html#method.registry). Changes apply to requests dispatched
+/// afterwards; dispatch reads the methods without taking a lock. Methods registered at runtime run through the
+/// server middlewares and use the server-wide timeout unless registered with their own, but are not listed in the
+/// generated documentation.
+#[derive(Clone)]
+pub struct Registry<M>
+where
+    M: Metadata,
+{
+    router: Arc<ArcSwap<BoxedRouter<M>>>,
+    middlewares: Vec<Arc<dyn Middleware<M>>>,
+}
+
+impl<M: Metadata> Registry<M> {
+    /// Register a method handler, replacing any method of the same name
+    ///
+    /// Takes the same handlers as [`ServerBuilder::with_method`](struct.ServerBuilder.html#method.with_method).
+    /// Returns whether a method was replaced.
+    pub fn register<N, S, E, T, F>(&self, name: N, handler: F) -> bool
+    where
+        N: Into<String>,
+        F: Factory<S, E, T, M> + Send + Sync + 'static,
+        S: Serialize + Send + 'static,
+        Error: From<E>,
+        E: 'static,
+        T: FromRequest<M> + Send + 'static,
+    {
+        self.register_middleware(name, handler, vec![])
+    }
+
+    /// Register a method handler with its own middlewares, replacing any method of the same name
+    ///
+    /// Returns whether a method was replaced.
+    pub fn register_middleware<N, S, E, T, F>(
+        &self,
+        name: N,
+        handler: F,
+        middlewares: Vec<Arc<dyn Middleware<M>>>,
+    ) -> bool
+    where
+        N: Into<String>,
+        F: Factory<S, E, T, M> + Send + Sync + 'static,
+        S: Serialize + Send + 'static,
+        Error: From<E>,
+        E: 'static,
+        T: FromRequest<M> + Send + 'static,
+    {
+        self.insert(name.into(), handler, middlewares, None)
+    }
+
+    /// Register a method handler with its own timeout, replacing any method of the same name
+    ///
+    /// The timeout overrides the server-wide default, as with
+    /// [`ServerBuilder::with_method_timeout`](struct.ServerBuilder.html#method.with_method_timeout).
+    /// Returns whether a method was replaced.
+    pub fn register_with_timeout<N, S, E, T, F>(
+        &self,
+        name: N,
+        handler: F,
+        timeout: Duration,
+    ) -> bool
+    where
+        N: Into<String>,
+        F: Factory<S, E, T, M> + Send + Sync + 'static,
+        S: Serialize + Send + 'static,
+        Error: From<E>,
+        E: 'static,
+        T: FromRequest<M> + Send + 'static,
+    {
+        self.insert(name.into(), handler, vec![], Some(timeout))
+    }
+
+    /// Register a method handler with its own middlewares and timeout, replacing any method of the same name
+    ///
+    /// Returns whether a method was replaced.
+    pub fn register_middleware_with_timeout<N, S, E, T, F>(
+        &self,
+        name: N,
+        handler: F,
+        middlewares: Vec<Arc<dyn Middleware<M>>>,
+        timeout: Duration,
+    ) -> bool
+    where
+        N: Into<String>,
+        F: Factory<S, E, T, M> + Send + Sync + 'static,
+        S: Serialize + Send + 'static,
+        Error: From<E>,
+        E: 'static,
+        T: FromRequest<M> + Send + 'static,
+    {
+        self.insert(name.into(), handler, middlewares, Some(timeout))
+    }
+
+    fn insert<S, E, T, F>(
+        &self,
+        name: String,
+        handler: F,
+        middlewares: Vec<Arc<dyn Middleware<M>>>,
+        timeout: Option<Duration>,
+    ) -> bool
+    where
+        F: Factory<S, E, T, M> + Send + Sync + 'static,
+        S: Serialize + Send + 'static,
+        Error: From<E>,
+        E: 'static,
+        T: FromRequest<M> + Send + 'static,
+    {
+        let mut new_middlewares = self.middlewares.clone();
+        new_middlewares.extend(middlewares);
+        let route =
+            Route { handler: Handler::new(handler).into(), middlewares: new_middlewares, timeout };
+
+        let mut replaced = false;
+        self.router.rcu(|router| {
+            let mut router = router.boxed_clone();
+            replaced = router.insert(name.clone(), route.clone()).is_some();
+            router
+        });
+        replaced
+    }
+
+    /// Unregister a method, so it is answered with `METHOD_NOT_FOUND` (or the fallback) from then on
+    ///
+    /// Returns whether the method was registered.
+    pub fn unregister(&self, name: &str) -> bool {
+        let mut removed = false;
+        self.router.rcu(|router| {
+            let mut router = router.boxed_clone();
+            removed = router.remove(name).is_some();
+            router
+        });
+        removed
+    }
+
+    /// Whether calls to `name` currently reach a registered method
+    pub fn contains(&self, name: &str) -> bool {
+        self.router.load().get(name).is_some()
+    }
+}
+
 impl<M> Server<M>
 where
     M: Metadata,
 {
-    #[cfg(feature = "actix-web-v1-integration")]
-    fn handle_bytes_compat(
+    /// A handle to change the methods of this server while it is running
+    pub fn registry(&self) -> Registry<M> {
+        Registry { router: Arc::clone(&self.router), middlewares: self.middlewares.clone() }
+    }
+
+    /// Handle raw bytes, returning a `futures` 0.1 compatible future
+    #[cfg(feature = "futures-v01")]
+    pub fn handle_bytes_compat(
         &self,
         bytes: Bytes,
         metadata: M,
-    ) -> impl futures_v01::Future<Item = ResponseObjects, Error = ()> {
-        self.handle_bytes(bytes, metadata).unit_error().boxed().compat()
+    ) -> impl futures_v01::Future<Item = ResponseObjects, Error = ()> + '_ {
+        use futures::TryFutureExt;
+        self.handle_bytes(bytes, metadata, None).unit_error().boxed().compat()
     }
 
     /// Handle requests, and return appropriate responses
@@ -151,49 +727,143 @@ where
         &self,
         req: I,
         metadata: M,
+    ) -> impl Future<Output = ResponseObjects> + '_ {
+        self.handle_in_session(req, metadata, None)
+    }
+
+    /// Handle requests received on a stateful connection, making `session` available to handlers
+    pub(crate) fn handle_in_session<I: Into<RequestKind>>(
+        &self,
+        req: I,
+        metadata: M,
+        session: Option<Session>,
     ) -> impl Future<Output = ResponseObjects> + '_ {
         match req.into() {
-            RequestKind::Bytes(bytes) => future::Either::Left(self.handle_bytes(bytes, metadata)),
-            RequestKind::RequestObject(req) => future::Either::Right(future::Either::Left(
-                self.handle_request_object(req, metadata).map(From::from),
+            RequestKind::Bytes(bytes) => future::Either::Left(future::Either::Left(
+                self.handle_bytes(bytes, metadata, session),
             )),
-            RequestKind::ManyRequestObjects(reqs) => future::Either::Right(future::Either::Right(
-                self.handle_many_request_objects(reqs, metadata).map(From::from),
+            RequestKind::RequestObject(req) => future::Either::Right(future::Either::Left(
+                self.handle_request_object(req, metadata, session).map(From::from),
             )),
+            RequestKind::ManyRequestObjects(reqs) => match self.limits.check_batch(reqs.len()) {
+                Ok(()) => future::Either::Right(future::Either::Right(
+                    self.handle_many_request_objects(
+                        reqs.into_iter().map(Ok).collect(),
+                        metadata,
+                        session,
+                    )
+                    .map(From::from),
+                )),
+                Err(e) => future::Either::Left(future::Either::Right(future::ready(
+                    ResponseObjects::One(ResponseObject::error(e, Id::Null)),
+                ))),
+            },
         }
     }
 
-    fn handle_request_object(
+    async fn handle_request_object(
         &self,
-        req: RequestObject,
+        mut req: RequestObject,
         metadata: M,
-    ) -> impl Future<Output = SingleResponseObject> + '_ {
+        session: Option<Session>,
+    ) -> SingleResponseObject {
         let opt_id = req.id.clone();
+        let mut in_flight = None;
 
-        if let Some(route) = self.router.get(req.method.as_ref()) {
-            let next = Next { endpoint: &route.handler, next_middleware: &route.middlewares };
+        req.extensions.insert(self.data.clone());
 
-            let out = next.run(req, metadata).then(|res| match res {
-                Ok(val) => future::ready(SingleResponseObject::result(val, opt_id)),
-                Err(e) => future::ready(SingleResponseObject::error(e, opt_id)),
-            });
-            future::Either::Left(out)
-        } else {
-            future::Either::Right(future::ready(SingleResponseObject::error(
-                Error::METHOD_NOT_FOUND,
-                opt_id,
-            )))
+        if let Some(session) = session {
+            if req.method.as_ref() == CANCEL_METHOD {
+                return match Params::<CancelParams>::from_request(&req, metadata).await {
+                    Ok(Params(params)) => {
+                        SingleResponseObject::result(Box::new(session.cancel(&params.id)), opt_id)
+                    }
+                    Err(e) => SingleResponseObject::error(e, opt_id),
+                };
+            }
+
+            if !opt_id.is_null() {
+                let (guard, token) = session.track(opt_id.clone());
+                req.extensions.insert(token.clone());
+                in_flight = Some((guard, token));
+            }
+
+            req.extensions.insert(session);
+        }
+
+        let router = self.router.load_full();
+        let route = match router.get(req.method.as_ref()).or(self.fallback.as_ref()) {
+            Some(route) => route,
+            None => return SingleResponseObject::error(Error::METHOD_NOT_FOUND, opt_id),
+        };
+
+        let next = Next { endpoint: &route.handler, next_middleware: &route.middlewares };
+
+        let run = async move {
+            let _permit = match self.concurrency {
+                Some(ref concurrency) => Some(concurrency.acquire().await),
+                None => None,
+            };
+            with_deadline(next.run(req, metadata), route.timeout.or(self.timeout)).await
+        };
+
+        let res = match in_flight {
+            Some((_guard, token)) => {
+                let cancelled = token.cancelled();
+                pin_mut!(run);
+                pin_mut!(cancelled);
+
+                match future::select(run, cancelled).await {
+                    future::Either::Left((res, _)) => res,
+                    future::Either::Right(_) => Err(Error::REQUEST_CANCELLED),
+                }
+            }
+            None => run.await,
+        };
+
+        match res {
+            Ok(val) => SingleResponseObject::result(val, opt_id),
+            Err(e) => SingleResponseObject::error(e, opt_id),
         }
     }
 
-    fn handle_many_request_objects<I: IntoIterator<Item = RequestObject>>(
+    /// Handle the items of a batch, where items that are not valid request objects carry their error response
+    fn handle_many_request_objects(
         &self,
-        reqs: I,
+        items: Vec<Result<RequestObject, ResponseObject>>,
         metadata: M,
+        session: Option<Session>,
     ) -> impl Future<Output = ManyResponseObjects> + '_ {
-        reqs.into_iter()
-            .map(|r| self.handle_request_object(r, metadata.clone()))
-            .collect::<futures::stream::FuturesUnordered<_>>()
+        let limit = match self.batch_concurrency {
+            BatchConcurrency::Unbounded => items.len().max(1),
+            BatchConcurrency::Limit(limit) => limit.max(1),
+            BatchConcurrency::Sequential => 1,
+        };
+
+        // unless ordered, errors for invalid items go after all the other responses
+        let (items, errs) = if self.ordered_batches {
+            (items, vec![])
+        } else {
+            let (okays, errs) = items.into_iter().partition::<Vec<_>, _>(Result::is_ok);
+            (okays, errs.into_iter().filter_map(Result::err).collect())
+        };
+
+        let responses = futures::stream::iter(items).map(move |item| match item {
+            Ok(r) => future::Either::Left(self.handle_request_object(
+                r,
+                metadata.clone(),
+                session.clone(),
+            )),
+            Err(res) => future::Either::Right(future::ready(SingleResponseObject::One(res))),
+        });
+
+        let responses = if self.ordered_batches {
+            responses.buffered(limit).left_stream()
+        } else {
+            responses.buffer_unordered(limit).right_stream()
+        };
+
+        responses
             .filter_map(|res| async move {
                 match res {
                     SingleResponseObject::One(r) => Some(r),
@@ -201,7 +871,8 @@ where
                 }
             })
             .collect::<Vec<_>>()
-            .map(|vec| {
+            .map(|mut vec| {
+                vec.extend(errs);
                 if vec.is_empty() {
                     ManyResponseObjects::Empty
                 } else {
@@ -214,7 +885,14 @@ where
         &self,
         bytes: Bytes,
         metadata: M,
+        session: Option<Session>,
     ) -> impl Future<Output = ResponseObjects> + '_ {
+        if let Err(e) = self.limits.check_payload(bytes.as_ref()) {
+            return future::Either::Left(future::ready(ResponseObjects::One(
+                ResponseObject::error(e, Id::Null),
+            )));
+        }
+
         if let Ok(raw_values) = OneOrManyRawValues::try_from_slice(bytes.as_ref()) {
             match raw_values {
                 OneOrManyRawValues::Many(raw_reqs) => {
@@ -224,51 +902,27 @@ where
                         )));
                     }
 
-                    let (okays, errs) = raw_reqs
-                        .into_iter()
-                        .map(|x| {
-                            serde_json::from_str::<BytesRequestObject>(x.get())
-                                .map(RequestObject::from)
-                        })
-                        .partition::<Vec<_>, _>(|x| x.is_ok());
+                    if let Err(e) = self.limits.check_batch(raw_reqs.len()) {
+                        return future::Either::Left(future::ready(ResponseObjects::One(
+                            ResponseObject::error(e, Id::Null),
+                        )));
+                    }
 
-                    let errs = errs
-                        .into_iter()
-                        .map(|_| ResponseObject::error(Error::INVALID_REQUEST, Id::Null))
-                        .collect::<Vec<_>>();
+                    let items = raw_reqs.into_iter().map(|x| self.parse_request(x)).collect();
 
                     future::Either::Right(future::Either::Left(
-                        self.handle_many_request_objects(okays.into_iter().flatten(), metadata)
-                            .map(|res| match res {
-                                ManyResponseObjects::Many(mut many) => {
-                                    many.extend(errs);
-                                    ResponseObjects::Many(many)
-                                }
-                                ManyResponseObjects::Empty => {
-                                    if errs.is_empty() {
-                                        ResponseObjects::Empty
-                                    } else {
-                                        ResponseObjects::Many(errs)
-                                    }
-                                }
-                            }),
+                        self.handle_many_request_objects(items, metadata, session).map(From::from),
                     ))
                 }
-                OneOrManyRawValues::One(raw_req) => {
-                    match serde_json::from_str::<BytesRequestObject>(raw_req.get())
-                        .map(RequestObject::from)
-                    {
-                        Ok(rn) => future::Either::Right(future::Either::Right(
-                            self.handle_request_object(rn, metadata).map(|res| match res {
-                                SingleResponseObject::One(r) => ResponseObjects::One(r),
-                                _ => ResponseObjects::Empty,
-                            }),
-                        )),
-                        Err(_) => future::Either::Left(future::ready(ResponseObjects::One(
-                            ResponseObject::error(Error::INVALID_REQUEST, Id::Null),
-                        ))),
-                    }
-                }
+                OneOrManyRawValues::One(raw_req) => match self.parse_request(raw_req) {
+                    Ok(rn) => future::Either::Right(future::Either::Right(
+                        self.handle_request_object(rn, metadata, session).map(|res| match res {
+                            SingleResponseObject::One(r) => ResponseObjects::One(r),
+                            _ => ResponseObjects::Empty,
+                        }),
+                    )),
+                    Err(res) => future::Either::Left(future::ready(ResponseObjects::One(res))),
+                },
             }
         } else {
             future::Either::Left(future::ready(ResponseObjects::One(ResponseObject::error(
@@ -279,6 +933,228 @@ where
     }
 }
 
+impl<M> Server<M>
+where
+    M: Metadata,
+{
+    /// Parse a single request object, or build the error response for an invalid one
+    fn parse_request(&self, raw: &RawValue) -> Result<RequestObject, ResponseObject> {
+        let parsed = if self.strict && !BytesRequestObject::is_strictly_valid(raw.get()) {
+            None
+        } else {
+            serde_json::from_str::<BytesRequestObject>(raw.get()).ok()
+        };
+
+        parsed.map(RequestObject::from).ok_or_else(|| {
+            let id = BytesRequestObject::recover_id(raw.get());
+            ResponseObject::error(Error::INVALID_REQUEST, id)
+        })
+    }
+}
+
+/// Whether a method name could match both `a` and `b`, where a trailing `*` makes a name a prefix pattern
+fn overlaps(a: &str, b: &str) -> bool {
+    match (a.strip_suffix('*'), b.strip_suffix('*')) {
+        (Some(a), Some(b)) => a.starts_with(b) || b.starts_with(a),
+        (Some(stem), None) => b.starts_with(stem),
+        (None, Some(stem)) => a.starts_with(stem),
+        (None, None) => a == b,
+    }
+}
+
+/// Run `fut`, abandoning it with a timeout error if it is still pending after `timeout`
+async fn with_deadline<F>(fut: F, timeout: Option<Duration>) -> Result<BoxedSerialize, Error>
+where
+    F: Future<Output = Result<BoxedSerialize, Error>>,
+{
+    let timeout = match timeout {
+        Some(timeout) => timeout,
+        None => return fut.await,
+    };
+
+    let start = Instant::now();
+    pin_mut!(fut);
+
+    match future::select(fut, Delay::new(timeout)).await {
+        future::Either::Left((res, _)) => res,
+        future::Either::Right(_) => Err(Error::timeout(start.elapsed())),
+    }
+}
+
+#[cfg(feature = "actix-web-v2-integration")]
+impl<M> Server<M>
+where
+    M: Metadata,
+{
+    /// Convert the server into an `actix-web` service factory, e.g. for use with `actix_web::web::service(..).finish(..)`
+    ///
+    /// The `metadata` closure is called once per HTTP request to build the `Metadata` value passed to handlers,
+    /// so handlers can see things like the peer address and headers.
+    pub fn into_web_service<F>(
+        self: Arc<Self>,
+        metadata: F,
+    ) -> impl actix_service::ServiceFactory<
+        Config = (),
+        Request = actix_web::dev::ServiceRequest,
+        Response = actix_web::dev::ServiceResponse,
+        Error = actix_web::Error,
+        InitError = (),
+    >
+    where
+        F: Fn(&actix_web::HttpRequest) -> M + 'static,
+    {
+        let metadata = Arc::new(metadata);
+
+        let inner = move |req: actix_web::dev::ServiceRequest| {
+            let server = Arc::clone(&self);
+            let metadata = Arc::clone(&metadata);
+
+            async move {
+                let (req, mut payload) = req.into_parts();
+
+                let mut body = Vec::new();
+                while let Some(chunk) = payload.next().await {
+                    body.extend_from_slice(&chunk?);
+                    if server.limits.payload_exceeded(body.len()) {
+                        break;
+                    }
+                }
+
+                let metadata = (metadata)(&req);
+
+                match server.handle(Bytes::from(body), metadata).await {
+                    ResponseObjects::Empty => Ok(actix_web::dev::ServiceResponse::new(
+                        req,
+                        actix_web::HttpResponse::NoContent().finish(),
+                    )),
+                    json => Ok(actix_web::dev::ServiceResponse::new(
+                        req,
+                        actix_web::HttpResponse::Ok().json(json),
+                    )),
+                }
+            }
+        };
+
+        actix_service::fn_service::<_, _, _, _, _, ()>(inner)
+    }
+}
+
+#[cfg(feature = "hyper-integration")]
+impl<M> Server<M>
+where
+    M: Metadata,
+{
+    /// Convert the server into a `hyper` service
+    ///
+    /// The `metadata` closure is called once per HTTP request to build the `Metadata` value passed to handlers.
+    pub fn into_hyper_web_service<F>(self: Arc<Self>, metadata: F) -> Hyper<M, F>
+    where
+        F: Fn(&hyper::Request<hyper::Body>) -> M + Send + Sync + 'static,
+    {
+        Hyper { server: self, metadata: Arc::new(metadata) }
+    }
+}
+
+/// `hyper` service wrapping a `Server`, created with `Server::into_hyper_web_service`
+///
+/// Serves as both the per-request service and the `MakeService` passed to `hyper::Server::serve`.
+#[cfg(feature = "hyper-integration")]
+pub struct Hyper<M, F>
+where
+    M: Metadata,
+{
+    server: Arc<Server<M>>,
+    metadata: Arc<F>,
+}
+
+#[cfg(feature = "hyper-integration")]
+impl<M: Metadata, F> Clone for Hyper<M, F> {
+    fn clone(&self) -> Self {
+        Hyper { server: Arc::clone(&self.server), metadata: Arc::clone(&self.metadata) }
+    }
+}
+
+#[cfg(feature = "hyper-integration")]
+impl<M, F> tower_service::Service<hyper::Request<hyper::Body>> for Hyper<M, F>
+where
+    M: Metadata,
+    F: Fn(&hyper::Request<hyper::Body>) -> M + Send + Sync + 'static,
+{
+    type Response = hyper::Response<hyper::Body>;
+    type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+    type Future = std::pin::Pin<
+        Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send + 'static>,
+    >;
+
+    fn poll_ready(
+        &mut self,
+        _: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        std::task::Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: hyper::Request<hyper::Body>) -> Self::Future {
+        let server = Arc::clone(&self.server);
+        let metadata = (self.metadata)(&req);
+
+        Box::pin(async move {
+            if req.method() != hyper::Method::POST {
+                return hyper::Response::builder()
+                    .status(hyper::StatusCode::METHOD_NOT_ALLOWED)
+                    .header(hyper::header::ALLOW, "POST")
+                    .body(hyper::Body::empty())
+                    .map_err(Box::from);
+            }
+
+            let mut body = req.into_body();
+            let mut bytes = Vec::new();
+            while let Some(chunk) = hyper::body::HttpBody::data(&mut body).await {
+                bytes.extend_from_slice(&chunk?);
+                if server.limits.payload_exceeded(bytes.len()) {
+                    break;
+                }
+            }
+
+            match server.handle(Bytes::from(bytes), metadata).await {
+                ResponseObjects::Empty => hyper::Response::builder()
+                    .status(hyper::StatusCode::NO_CONTENT)
+                    .body(hyper::Body::empty())
+                    .map_err(Box::from),
+                json => {
+                    let body = serde_json::to_vec(&json)?;
+                    hyper::Response::builder()
+                        .status(hyper::StatusCode::OK)
+                        .header(hyper::header::CONTENT_TYPE, "application/json")
+                        .body(hyper::Body::from(body))
+                        .map_err(Box::from)
+                }
+            }
+        })
+    }
+}
+
+#[cfg(feature = "hyper-integration")]
+impl<'a, M, F> tower_service::Service<&'a hyper::server::conn::AddrStream> for Hyper<M, F>
+where
+    M: Metadata,
+    F: Send + Sync + 'static,
+{
+    type Response = Hyper<M, F>;
+    type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+    type Future = future::Ready<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(
+        &mut self,
+        _: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        std::task::Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _: &'a hyper::server::conn::AddrStream) -> Self::Future {
+        future::ok(self.clone())
+    }
+}
+
 pub trait Metadata: Clone + Send + 'static {}
 
 impl Metadata for () {}
@@ -0,0 +1,173 @@
+//! Reading and writing framed messages on byte streams
+
+use super::{serve_messages, Frame, Framing, Shutdown};
+use crate::server::{Metadata, Server};
+
+use futures::{sink, stream};
+use std::io;
+use std::sync::Arc;
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader,
+};
+
+/// Longest header line accepted with `Framing::ContentLength`
+const MAX_HEADER_LINE: usize = 8 * 1024;
+
+impl Framing {
+    /// Read the next frame, returning `None` on a clean EOF
+    ///
+    /// Frames longer than `max_len` bytes are never buffered: the rest of a longer line, or the body
+    /// of a `Content-Length` frame announcing more, is discarded as it arrives and `Frame::Oversized` returned.
+    pub(crate) async fn read_frame<R>(
+        &self,
+        reader: &mut R,
+        max_len: usize,
+    ) -> io::Result<Option<Frame>>
+    where
+        R: AsyncBufRead + Unpin,
+    {
+        match self {
+            Framing::Lines => loop {
+                let mut line = Vec::new();
+                // room for the frame and its `\r\n`, so a longer line is noticed without buffering it
+                let cap = max_len.saturating_add(2);
+                if reader.take(cap as u64).read_until(b'\n', &mut line).await? == 0 {
+                    return Ok(None);
+                }
+                if line.len() == cap && line.last() != Some(&b'\n') {
+                    // the rest of the line, in pieces no larger than the frame itself
+                    loop {
+                        line.clear();
+                        if reader.take(cap as u64).read_until(b'\n', &mut line).await? == 0
+                            || line.last() == Some(&b'\n')
+                        {
+                            return Ok(Some(Frame::Oversized { limit: max_len }));
+                        }
+                    }
+                }
+                while let Some(b'\n') | Some(b'\r') = line.last() {
+                    line.pop();
+                }
+                if line.len() > max_len {
+                    return Ok(Some(Frame::Oversized { limit: max_len }));
+                }
+                if !line.iter().all(u8::is_ascii_whitespace) {
+                    return Ok(Some(Frame::Message(line)));
+                }
+            },
+            Framing::ContentLength => {
+                let mut content_length = None;
+                let mut started = false;
+
+                loop {
+                    let mut line = Vec::new();
+                    let read =
+                        reader.take(MAX_HEADER_LINE as u64).read_until(b'\n', &mut line).await?;
+                    if read == 0 {
+                        if started {
+                            return Err(io::ErrorKind::UnexpectedEof.into());
+                        }
+                        return Ok(None);
+                    }
+                    started = true;
+                    if line.len() == MAX_HEADER_LINE && line.last() != Some(&b'\n') {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "header line too long",
+                        ));
+                    }
+
+                    let line = std::str::from_utf8(&line)
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+                        .trim_end_matches(&['\n', '\r'][..]);
+
+                    if line.is_empty() {
+                        break;
+                    }
+
+                    let (name, value) = line.split_at(line.find(':').ok_or_else(|| {
+                        io::Error::new(io::ErrorKind::InvalidData, "malformed header")
+                    })?);
+
+                    if name.trim().eq_ignore_ascii_case("content-length") {
+                        let len = value[1..]
+                            .trim()
+                            .parse::<usize>()
+                            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                        content_length = Some(len);
+                    }
+                }
+
+                let len = content_length.ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length header")
+                })?;
+
+                if len > max_len {
+                    let skipped =
+                        tokio::io::copy(&mut reader.take(len as u64), &mut tokio::io::sink())
+                            .await?;
+                    if skipped < len as u64 {
+                        return Err(io::ErrorKind::UnexpectedEof.into());
+                    }
+                    return Ok(Some(Frame::Oversized { limit: max_len }));
+                }
+
+                let mut body = Vec::new();
+                reader.take(len as u64).read_to_end(&mut body).await?;
+                if body.len() < len {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+                Ok(Some(Frame::Message(body)))
+            }
+        }
+    }
+
+    /// Write a single frame and flush it
+    pub(crate) async fn write_frame<W>(&self, writer: &mut W, frame: &[u8]) -> io::Result<()>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        match self {
+            Framing::Lines => {
+                writer.write_all(frame).await?;
+                writer.write_all(b"\n").await?;
+            }
+            Framing::ContentLength => {
+                writer
+                    .write_all(format!("Content-Length: {}\r\n\r\n", frame.len()).as_bytes())
+                    .await?;
+                writer.write_all(frame).await?;
+            }
+        }
+        writer.flush().await
+    }
+}
+
+/// Serve a single byte stream connection until EOF, an IO error, or `shutdown`
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn serve_connection<M, R, W>(
+    server: Arc<Server<M>>,
+    reader: R,
+    writer: W,
+    metadata: M,
+    framing: Framing,
+    max_frame: usize,
+    max_in_flight: usize,
+    shutdown: Shutdown,
+) -> io::Result<()>
+where
+    M: Metadata,
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let incoming = stream::unfold(BufReader::new(reader), move |mut reader| async move {
+        framing.read_frame(&mut reader, max_frame).await.transpose().map(|frame| (frame, reader))
+    });
+
+    let outgoing = sink::unfold(writer, move |mut writer, frame: Vec<u8>| async move {
+        framing.write_frame(&mut writer, &frame).await?;
+        Ok::<_, io::Error>(writer)
+    });
+
+    serve_messages(server, incoming, outgoing, metadata, max_in_flight, shutdown).await
+}
@@ -0,0 +1,215 @@
+//! Accept loop shared by listening transports
+
+use super::Shutdown;
+
+use futures::{future::Future, pin_mut, stream::Stream, StreamExt};
+use futures_timer::Delay;
+use std::io;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+#[cfg(any(feature = "tcp-transport", all(unix, feature = "unix-transport")))]
+use super::{serve_connection, Framing, DEFAULT_MAX_FRAME_BYTES, DEFAULT_MAX_IN_FLIGHT};
+#[cfg(any(feature = "tcp-transport", all(unix, feature = "unix-transport")))]
+use crate::server::{Metadata, Server};
+#[cfg(any(feature = "tcp-transport", all(unix, feature = "unix-transport")))]
+use futures::future;
+#[cfg(any(feature = "tcp-transport", all(unix, feature = "unix-transport")))]
+use std::{marker::PhantomData, sync::Arc};
+#[cfg(any(feature = "tcp-transport", all(unix, feature = "unix-transport")))]
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// How long to wait before accepting again after an error that is not about a single connection,
+/// e.g. running out of file descriptors, which would otherwise fail again right away
+const ACCEPT_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Serve connections from `incoming` until `signal` resolves, then wait for open connections to drain
+///
+/// `connect` turns each accepted connection into a future to be spawned; it is given a
+/// [`Shutdown`](struct.Shutdown.html) which the connection should observe.
+pub(crate) async fn serve_until<I, T, F, C, S>(
+    incoming: I,
+    mut connect: F,
+    signal: S,
+) -> io::Result<()>
+where
+    I: Stream<Item = io::Result<T>>,
+    F: FnMut(T, Shutdown) -> C,
+    C: Future<Output = ()> + Send + 'static,
+    S: Future<Output = ()>,
+{
+    let (stop_tx, stop_rx) = Shutdown::new();
+    let (done_tx, mut done_rx) = mpsc::channel::<()>(1);
+
+    pin_mut!(incoming);
+    pin_mut!(signal);
+
+    loop {
+        let conn = tokio::select! {
+            conn = incoming.next() => conn,
+            _ = &mut signal => None,
+        };
+
+        match conn {
+            Some(Ok(conn)) => {
+                let conn = connect(conn, stop_rx.clone());
+                let done_tx = done_tx.clone();
+                tokio::spawn(async move {
+                    conn.await;
+                    drop(done_tx);
+                });
+            }
+            Some(Err(e)) if is_connection_error(&e) => {
+                log::debug!("failed to accept connection: {}", e)
+            }
+            Some(Err(e)) => {
+                log::warn!("failed to accept connection, retrying in {:?}: {}", ACCEPT_BACKOFF, e);
+                tokio::select! {
+                    _ = Delay::new(ACCEPT_BACKOFF) => {}
+                    _ = &mut signal => break,
+                }
+            }
+            None => break,
+        }
+    }
+
+    let _ = stop_tx.broadcast(true);
+    drop(done_tx);
+    done_rx.recv().await;
+    Ok(())
+}
+
+/// Whether an accept error only concerns the connection being accepted, so the next one can be accepted right away
+fn is_connection_error(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionReset
+    )
+}
+
+/// A listening socket whose connections are served by a [`StreamTransport`](struct.StreamTransport.html)
+#[cfg(any(feature = "tcp-transport", all(unix, feature = "unix-transport")))]
+pub trait Listener {
+    /// An accepted connection
+    type Conn: AsyncRead + AsyncWrite + Send + 'static;
+    /// What the transport's `metadata` closure is told about the other end of a connection
+    type Peer: std::fmt::Debug + Clone + Send + 'static;
+
+    fn incoming(&mut self) -> impl Stream<Item = io::Result<Self::Conn>> + '_;
+    fn peer(conn: &Self::Conn) -> io::Result<Self::Peer>;
+}
+
+/// Serves a `Server` over the byte stream connections of a [`Listener`](trait.Listener.html),
+/// by default with one JSON document per line
+///
+/// The `metadata` closure is called once per connection with what the listener knows about the peer;
+/// the resulting value is cloned for every request on that connection.
+#[cfg(any(feature = "tcp-transport", all(unix, feature = "unix-transport")))]
+pub struct StreamTransport<L, M, F>
+where
+    M: Metadata,
+{
+    server: Arc<Server<M>>,
+    metadata: F,
+    framing: Framing,
+    max_frame: usize,
+    max_in_flight: usize,
+    listener: PhantomData<fn(L)>,
+}
+
+#[cfg(any(feature = "tcp-transport", all(unix, feature = "unix-transport")))]
+impl<L, M, F> StreamTransport<L, M, F>
+where
+    L: Listener,
+    M: Metadata,
+    F: Fn(L::Peer) -> M,
+{
+    pub fn new(server: Arc<Server<M>>, metadata: F) -> Self {
+        StreamTransport {
+            server,
+            metadata,
+            framing: Framing::default(),
+            max_frame: DEFAULT_MAX_FRAME_BYTES,
+            max_in_flight: DEFAULT_MAX_IN_FLIGHT,
+            listener: PhantomData,
+        }
+    }
+
+    /// Set the framing used on every connection
+    pub fn with_framing(mut self, framing: Framing) -> Self {
+        self.framing = framing;
+        self
+    }
+
+    /// Set the largest frame accepted, in bytes, [`DEFAULT_MAX_FRAME_BYTES`](constant.DEFAULT_MAX_FRAME_BYTES.html) by default
+    ///
+    /// Larger frames are never read into memory: they are skipped as they arrive and answered with `INVALID_REQUEST`.
+    pub fn with_max_frame_bytes(mut self, limit: usize) -> Self {
+        self.max_frame = limit;
+        self
+    }
+
+    /// Set how many requests are handled at once on each connection, [`DEFAULT_MAX_IN_FLIGHT`](constant.DEFAULT_MAX_IN_FLIGHT.html) by default
+    ///
+    /// Once the limit is reached, a connection stops reading until one of its requests has been answered;
+    /// a limit of 0 is treated as 1.
+    pub fn with_max_in_flight(mut self, limit: usize) -> Self {
+        self.max_in_flight = limit;
+        self
+    }
+
+    /// Accept and serve connections forever
+    pub async fn serve(self, listener: L) -> io::Result<()> {
+        self.serve_with_shutdown(listener, future::pending()).await
+    }
+
+    /// Accept and serve connections until `signal` resolves
+    ///
+    /// Once `signal` resolves no new connections are accepted, open connections stop reading,
+    /// and this returns after their in-flight requests have been answered.
+    pub async fn serve_with_shutdown<S>(self, mut listener: L, signal: S) -> io::Result<()>
+    where
+        S: Future<Output = ()>,
+    {
+        let StreamTransport { server, metadata, framing, max_frame, max_in_flight, .. } = self;
+
+        serve_until(
+            listener.incoming(),
+            |conn, shutdown| {
+                let server = Arc::clone(&server);
+                let peer = match L::peer(&conn) {
+                    Ok(peer) => Some((peer.clone(), metadata(peer))),
+                    Err(e) => {
+                        log::warn!("failed to identify peer: {}", e);
+                        None
+                    }
+                };
+                async move {
+                    let (peer, metadata) = match peer {
+                        Some(peer) => peer,
+                        None => return,
+                    };
+                    let (reader, writer) = tokio::io::split(conn);
+                    if let Err(e) = serve_connection(
+                        server,
+                        reader,
+                        writer,
+                        metadata,
+                        framing,
+                        max_frame,
+                        max_in_flight,
+                        shutdown,
+                    )
+                    .await
+                    {
+                        log::debug!("connection from {:?} closed with error: {}", peer, e);
+                    }
+                }
+            },
+            signal,
+        )
+        .await
+    }
+}
@@ -0,0 +1,213 @@
+//! Transports serving a [`Server`](../struct.Server.html) over long-lived connections
+//!
+//! Each connection reads framed requests, dispatches them concurrently through
+//! [`Server::handle`](../struct.Server.html#method.handle), and writes back serialized responses
+//! in completion order. Nothing is written for notifications.
+//!
+//! Connections are stateful, so subscriptions added with
+//! [`ServerBuilder::with_subscription`](../server/struct.ServerBuilder.html#method.with_subscription)
+//! can push notifications to the client until it unsubscribes or disconnects.
+//!
+//! Clients can also cancel an in-flight request by sending a
+//! [`CANCEL_METHOD`](../cancel/constant.CANCEL_METHOD.html) notification with its id.
+
+use crate::error::Error;
+use crate::pubsub::Session;
+use crate::response::{ResponseObject, ResponseObjects};
+use crate::server::{Metadata, Server};
+use crate::Id;
+
+use futures::{channel::mpsc, pin_mut, sink::Sink, stream::Stream, SinkExt, StreamExt};
+use std::sync::Arc;
+use tokio::sync::{watch, Semaphore};
+
+#[cfg(not(feature = "bytes-v04"))]
+use bytes::Bytes;
+
+#[cfg(feature = "bytes-v04")]
+use bytes_v04::Bytes;
+
+#[cfg(any(
+    feature = "stdio-transport",
+    feature = "tcp-transport",
+    all(unix, feature = "unix-transport")
+))]
+mod codec;
+#[cfg(any(
+    feature = "tcp-transport",
+    all(unix, feature = "unix-transport"),
+    feature = "ws-transport"
+))]
+mod listener;
+#[cfg(feature = "stdio-transport")]
+pub mod stdio;
+#[cfg(feature = "tcp-transport")]
+pub mod tcp;
+#[cfg(all(unix, feature = "unix-transport"))]
+pub mod unix;
+#[cfg(feature = "ws-transport")]
+pub mod ws;
+
+#[cfg(any(
+    feature = "stdio-transport",
+    feature = "tcp-transport",
+    all(unix, feature = "unix-transport")
+))]
+pub(crate) use codec::serve_connection;
+#[cfg(feature = "ws-transport")]
+pub(crate) use listener::serve_until;
+#[cfg(any(feature = "tcp-transport", all(unix, feature = "unix-transport")))]
+pub use listener::{Listener, StreamTransport};
+
+/// Number of serialized responses buffered per connection before handlers wait on the writer
+const RESPONSE_BUFFER: usize = 64;
+
+/// Default limit on the number of requests handled at once on a single connection
+///
+/// Once this many are in flight, the connection stops reading until one of them has
+/// been answered, so a client that does not read its responses cannot queue unbounded work.
+pub const DEFAULT_MAX_IN_FLIGHT: usize = 128;
+
+/// Default limit on the size of a single frame read from a connection, 16 MiB
+pub const DEFAULT_MAX_FRAME_BYTES: usize = 16 * 1024 * 1024;
+
+/// How individual messages are delimited on a byte stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Framing {
+    /// One JSON document per line, terminated by `\n` (a preceding `\r` is ignored)
+    #[default]
+    Lines,
+    /// Language Server Protocol style: `Content-Length: <n>` and optional other headers,
+    /// a blank line, then exactly `n` bytes of JSON
+    ContentLength,
+}
+
+/// A message read from a connection
+pub(crate) enum Frame {
+    Message(Vec<u8>),
+    /// A message over the transport's frame limit of `limit` bytes, which was not read into memory
+    #[cfg_attr(
+        not(any(
+            feature = "stdio-transport",
+            feature = "tcp-transport",
+            all(unix, feature = "unix-transport")
+        )),
+        allow(dead_code)
+    )]
+    Oversized {
+        limit: usize,
+    },
+}
+
+/// Shutdown signal shared between a listener and its connections
+#[derive(Clone)]
+pub(crate) struct Shutdown(watch::Receiver<bool>);
+
+impl Shutdown {
+    pub(crate) fn new() -> (watch::Sender<bool>, Self) {
+        let (tx, rx) = watch::channel(false);
+        (tx, Shutdown(rx))
+    }
+
+    /// Resolves once shutdown has been requested or the listener has gone away
+    pub(crate) async fn wait(mut self) {
+        while let Some(false) = self.0.recv().await {}
+    }
+}
+
+/// Serve a single message-oriented connection until `incoming` ends, yields an error, or `shutdown`
+///
+/// Up to `max_in_flight` requests are handled concurrently; once reading stops, in-flight requests
+/// are allowed to finish and their responses are sent before `outgoing` is closed.
+pub(crate) async fn serve_messages<M, I, O, E>(
+    server: Arc<Server<M>>,
+    incoming: I,
+    outgoing: O,
+    metadata: M,
+    max_in_flight: usize,
+    shutdown: Shutdown,
+) -> Result<(), E>
+where
+    M: Metadata,
+    I: Stream<Item = Result<Frame, E>>,
+    O: Sink<Vec<u8>, Error = E>,
+{
+    let (tx, rx) = mpsc::channel::<Vec<u8>>(RESPONSE_BUFFER);
+    let in_flight = Arc::new(Semaphore::new(max_in_flight.max(1)));
+    let session = Session::new(tx.clone());
+
+    let closing = session.clone();
+    let read = async move {
+        let read = async move {
+            let shutdown = shutdown.wait();
+            pin_mut!(incoming);
+            pin_mut!(shutdown);
+
+            loop {
+                let frame = tokio::select! {
+                    frame = incoming.next() => frame,
+                    _ = &mut shutdown => None,
+                };
+
+                let frame = match frame {
+                    Some(Ok(Frame::Message(frame))) => frame,
+                    Some(Ok(Frame::Oversized { limit })) => {
+                        let e = Error::invalid_request(format!(
+                            "frame exceeds the limit of {} bytes",
+                            limit
+                        ));
+                        let res = ResponseObjects::One(ResponseObject::error(e, Id::Null));
+                        match serde_json::to_vec(&res) {
+                            Ok(json) => {
+                                let _ = tx.clone().send(json).await;
+                            }
+                            Err(e) => log::error!("failed to serialize response: {}", e),
+                        }
+                        continue;
+                    }
+                    Some(Err(e)) => return Err(e),
+                    None => return Ok(()),
+                };
+
+                // held until the response is queued, so a writer that falls behind stops the reads
+                let permit = Arc::clone(&in_flight).acquire_owned().await;
+
+                let server = Arc::clone(&server);
+                let metadata = metadata.clone();
+                let session = session.for_frame();
+                let mut tx = tx.clone();
+
+                tokio::spawn(async move {
+                    let res = server
+                        .handle_in_session(Bytes::from(frame), metadata, Some(session.clone()))
+                        .await;
+                    match res {
+                        ResponseObjects::Empty => {}
+                        res => match serde_json::to_vec(&res) {
+                            Ok(json) => {
+                                let _ = tx.send(json).await;
+                            }
+                            Err(e) => log::error!("failed to serialize response: {}", e),
+                        },
+                    }
+                    drop(permit);
+
+                    // subscription handlers start once the ids they notify under are queued
+                    for work in session.take_deferred() {
+                        tokio::spawn(work);
+                    }
+                });
+            }
+        };
+
+        let res = read.await;
+        // nothing will unsubscribe from here on, so let subscription handlers see the connection close
+        closing.close();
+        res
+    };
+
+    let write = rx.map(Ok).forward(outgoing);
+
+    let (read, write) = futures::join!(read, write);
+    read.and(write)
+}
@@ -0,0 +1,90 @@
+//! Standard input/output transport, e.g. for editor tooling speaking the Language Server Protocol framing
+
+use super::{serve_connection, Framing, Shutdown, DEFAULT_MAX_FRAME_BYTES, DEFAULT_MAX_IN_FLIGHT};
+use crate::server::{Metadata, Server};
+
+use std::io;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// Serves a `Server` over stdin/stdout, by default with `Content-Length` framing
+///
+/// Use `with_framing(Framing::Lines)` for header-less, newline-delimited messages.
+pub struct StdioTransport<M>
+where
+    M: Metadata,
+{
+    server: Arc<Server<M>>,
+    metadata: M,
+    framing: Framing,
+    max_frame: usize,
+    max_in_flight: usize,
+}
+
+impl<M> StdioTransport<M>
+where
+    M: Metadata,
+{
+    pub fn new(server: Arc<Server<M>>, metadata: M) -> Self {
+        StdioTransport {
+            server,
+            metadata,
+            framing: Framing::ContentLength,
+            max_frame: DEFAULT_MAX_FRAME_BYTES,
+            max_in_flight: DEFAULT_MAX_IN_FLIGHT,
+        }
+    }
+
+    /// Set the framing used on stdin/stdout
+    pub fn with_framing(mut self, framing: Framing) -> Self {
+        self.framing = framing;
+        self
+    }
+
+    /// Set the largest frame accepted, in bytes, [`DEFAULT_MAX_FRAME_BYTES`](../constant.DEFAULT_MAX_FRAME_BYTES.html) by default
+    ///
+    /// Larger frames are never read into memory: they are skipped as they arrive and answered with `INVALID_REQUEST`.
+    pub fn with_max_frame_bytes(mut self, limit: usize) -> Self {
+        self.max_frame = limit;
+        self
+    }
+
+    /// Set how many requests are handled at once, [`DEFAULT_MAX_IN_FLIGHT`](../constant.DEFAULT_MAX_IN_FLIGHT.html) by default
+    ///
+    /// Once the limit is reached, reading stops until one of the requests has been answered;
+    /// a limit of 0 is treated as 1.
+    pub fn with_max_in_flight(mut self, limit: usize) -> Self {
+        self.max_in_flight = limit;
+        self
+    }
+
+    /// Serve requests from stdin until EOF
+    ///
+    /// Returns once stdin is closed and all in-flight requests have been answered.
+    pub async fn serve(self) -> io::Result<()> {
+        self.serve_io(tokio::io::stdin(), tokio::io::stdout()).await
+    }
+
+    /// Serve requests from `reader` until EOF, writing responses to `writer`,
+    /// e.g. the pipes of a child process instead of stdin/stdout
+    pub async fn serve_io<R, W>(self, reader: R, writer: W) -> io::Result<()>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let StdioTransport { server, metadata, framing, max_frame, max_in_flight } = self;
+        let (_stop, shutdown) = Shutdown::new();
+
+        serve_connection(
+            server,
+            reader,
+            writer,
+            metadata,
+            framing,
+            max_frame,
+            max_in_flight,
+            shutdown,
+        )
+        .await
+    }
+}
@@ -0,0 +1,27 @@
+//! TCP transport
+
+use super::{Listener, StreamTransport};
+
+use futures::stream::Stream;
+use std::io;
+use std::net::SocketAddr;
+use tokio::net::{TcpListener, TcpStream};
+
+/// Serves a `Server` over TCP, by default with one JSON document per line
+///
+/// The `metadata` closure is called once per connection with the peer address;
+/// the resulting value is cloned for every request on that connection.
+pub type TcpTransport<M, F> = StreamTransport<TcpListener, M, F>;
+
+impl Listener for TcpListener {
+    type Conn = TcpStream;
+    type Peer = SocketAddr;
+
+    fn incoming(&mut self) -> impl Stream<Item = io::Result<TcpStream>> + '_ {
+        TcpListener::incoming(self)
+    }
+
+    fn peer(conn: &TcpStream) -> io::Result<SocketAddr> {
+        conn.peer_addr()
+    }
+}
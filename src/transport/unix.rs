@@ -0,0 +1,77 @@
+//! Unix domain socket transport
+
+use super::{Listener, StreamTransport};
+
+use futures::stream::Stream;
+use std::io;
+use tokio::net::{UnixListener, UnixStream};
+
+/// Credentials of the process on the other end of a Unix domain socket
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PeerCredentials {
+    pub uid: u32,
+    pub gid: u32,
+    /// Process ID, where the platform reports it
+    pub pid: Option<i32>,
+}
+
+impl PeerCredentials {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn of(stream: &UnixStream) -> io::Result<Self> {
+        use std::os::unix::io::AsRawFd;
+
+        let mut ucred = libc::ucred { pid: 0, uid: 0, gid: 0 };
+        let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+
+        // SAFETY: the fd is borrowed from `stream`, which stays open for the whole call. `ucred` is a
+        // properly aligned, initialized `libc::ucred` that outlives the call, and `len` holds its exact
+        // size, so the kernel writes at most `len` bytes into it and reports how many it wrote in `len`.
+        let ret = unsafe {
+            libc::getsockopt(
+                stream.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_PEERCRED,
+                &mut ucred as *mut libc::ucred as *mut libc::c_void,
+                &mut len,
+            )
+        };
+
+        if ret != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // anything shorter would leave fields of `ucred` at their placeholder values
+        if len as usize != std::mem::size_of::<libc::ucred>() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("SO_PEERCRED returned {} bytes", len),
+            ));
+        }
+        Ok(PeerCredentials { uid: ucred.uid, gid: ucred.gid, pid: Some(ucred.pid) })
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    fn of(stream: &UnixStream) -> io::Result<Self> {
+        let cred = stream.peer_cred()?;
+        Ok(PeerCredentials { uid: cred.uid, gid: cred.gid, pid: None })
+    }
+}
+
+/// Serves a `Server` over a Unix domain socket, by default with one JSON document per line
+///
+/// The `metadata` closure is called once per connection with the peer's credentials,
+/// so middlewares can make authorization decisions; the resulting value is cloned
+/// for every request on that connection.
+pub type UnixTransport<M, F> = StreamTransport<UnixListener, M, F>;
+
+impl Listener for UnixListener {
+    type Conn = UnixStream;
+    type Peer = PeerCredentials;
+
+    fn incoming(&mut self) -> impl Stream<Item = io::Result<UnixStream>> + '_ {
+        UnixListener::incoming(self)
+    }
+
+    fn peer(conn: &UnixStream) -> io::Result<PeerCredentials> {
+        PeerCredentials::of(conn)
+    }
+}
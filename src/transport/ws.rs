@@ -0,0 +1,169 @@
+//! WebSocket transport
+
+use super::{serve_messages, serve_until, Frame, DEFAULT_MAX_FRAME_BYTES, DEFAULT_MAX_IN_FLIGHT};
+use crate::server::{Metadata, Server};
+
+use futures::{
+    future::{self, Future},
+    SinkExt, StreamExt,
+};
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::{self, protocol::WebSocketConfig, Message};
+
+/// The HTTP upgrade request that opened a WebSocket connection
+pub use tokio_tungstenite::tungstenite::handshake::server::Request as HandshakeRequest;
+
+/// Serves a `Server` over WebSocket, dispatching each text or binary message as a request
+///
+/// The `metadata` closure is called once per connection with the peer address and the
+/// upgrade request, so it can inspect headers; the resulting value is cloned for every
+/// request on that connection. Responses are sent as text messages.
+pub struct WsTransport<M, F>
+where
+    M: Metadata,
+{
+    server: Arc<Server<M>>,
+    metadata: Arc<F>,
+    max_message: usize,
+    max_in_flight: usize,
+}
+
+impl<M, F> WsTransport<M, F>
+where
+    M: Metadata,
+    F: Fn(SocketAddr, &HandshakeRequest) -> M + Send + Sync + 'static,
+{
+    pub fn new(server: Arc<Server<M>>, metadata: F) -> Self {
+        WsTransport {
+            server,
+            metadata: Arc::new(metadata),
+            max_message: DEFAULT_MAX_FRAME_BYTES,
+            max_in_flight: DEFAULT_MAX_IN_FLIGHT,
+        }
+    }
+
+    /// Set the largest message accepted, in bytes, [`DEFAULT_MAX_FRAME_BYTES`](../constant.DEFAULT_MAX_FRAME_BYTES.html) by default
+    ///
+    /// A connection sending a larger message is closed. Messages under this size but over the
+    /// server's payload limit are answered with `INVALID_REQUEST`.
+    pub fn with_max_message_bytes(mut self, limit: usize) -> Self {
+        self.max_message = limit;
+        self
+    }
+
+    /// Set how many requests are handled at once on each connection, [`DEFAULT_MAX_IN_FLIGHT`](../constant.DEFAULT_MAX_IN_FLIGHT.html) by default
+    ///
+    /// Once the limit is reached, a connection stops reading until one of its requests has been answered;
+    /// a limit of 0 is treated as 1.
+    pub fn with_max_in_flight(mut self, limit: usize) -> Self {
+        self.max_in_flight = limit;
+        self
+    }
+
+    /// Accept and serve connections forever
+    pub async fn serve(self, listener: TcpListener) -> io::Result<()> {
+        self.serve_with_shutdown(listener, future::pending()).await
+    }
+
+    /// Accept and serve connections until `signal` resolves
+    ///
+    /// Once `signal` resolves no new connections are accepted, open connections stop reading,
+    /// and this returns after their in-flight requests have been answered and the connections closed.
+    pub async fn serve_with_shutdown<S>(
+        self,
+        mut listener: TcpListener,
+        signal: S,
+    ) -> io::Result<()>
+    where
+        S: Future<Output = ()>,
+    {
+        let WsTransport { server, metadata, max_message, max_in_flight } = self;
+        let config = WebSocketConfig {
+            max_message_size: Some(max_message),
+            max_frame_size: Some(max_message),
+            ..WebSocketConfig::default()
+        };
+
+        serve_until(
+            listener.incoming(),
+            |stream, shutdown| {
+                let server = Arc::clone(&server);
+                let make_metadata = Arc::clone(&metadata);
+                async move {
+                    let peer = match stream.peer_addr() {
+                        Ok(peer) => peer,
+                        Err(e) => {
+                            log::warn!("failed to read peer address: {}", e);
+                            return;
+                        }
+                    };
+
+                    let mut metadata = None;
+                    #[allow(clippy::result_large_err)]
+                    let callback = |req: &HandshakeRequest, res| {
+                        metadata = Some(make_metadata(peer, req));
+                        Ok(res)
+                    };
+
+                    let ws = match tokio_tungstenite::accept_hdr_async_with_config(
+                        stream,
+                        callback,
+                        Some(config),
+                    )
+                    .await
+                    {
+                        Ok(ws) => ws,
+                        Err(e) => {
+                            log::debug!("websocket handshake with {} failed: {}", peer, e);
+                            return;
+                        }
+                    };
+
+                    let metadata = match metadata {
+                        Some(metadata) => metadata,
+                        None => return,
+                    };
+
+                    let (outgoing, incoming) = ws.split();
+
+                    let incoming = incoming.filter_map(|msg| {
+                        future::ready(match msg {
+                            Ok(Message::Text(text)) => Some(Ok(Frame::Message(text.into_bytes()))),
+                            Ok(Message::Binary(bytes)) => Some(Ok(Frame::Message(bytes))),
+                            Ok(Message::Ping(_)) | Ok(Message::Pong(_)) => None,
+                            Ok(Message::Close(_)) => None,
+                            Err(e) => Some(Err(e)),
+                        })
+                    });
+
+                    let outgoing = outgoing.with(|frame: Vec<u8>| {
+                        future::ready(
+                            String::from_utf8(frame)
+                                .map(Message::Text)
+                                .map_err(|_| tungstenite::Error::Utf8),
+                        )
+                    });
+
+                    match serve_messages(
+                        server,
+                        incoming,
+                        outgoing,
+                        metadata,
+                        max_in_flight,
+                        shutdown,
+                    )
+                    .await
+                    {
+                        Ok(()) | Err(tungstenite::Error::ConnectionClosed) => {}
+                        Err(e) => log::debug!("connection from {} closed with error: {}", peer, e),
+                    }
+                }
+            },
+            signal,
+        )
+        .await
+    }
+}
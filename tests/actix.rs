@@ -0,0 +1,69 @@
+//! Serving a `Server` as an `actix-web` service
+
+#![cfg(feature = "actix-web-v2-integration")]
+
+use actix_web::{http::StatusCode, test, web, App, HttpRequest};
+use jsonrpc_v2::{Error, Metadata, Params, Server};
+use serde_json::{json, Value};
+
+#[derive(Clone)]
+struct Caller(String);
+
+impl Metadata for Caller {}
+
+async fn echo(Params(params): Params<Value>, _: Caller) -> Result<Value, Error> {
+    Ok(params)
+}
+
+async fn whoami(Caller(caller): Caller) -> Result<String, Error> {
+    Ok(caller)
+}
+
+fn caller(req: &HttpRequest) -> Caller {
+    let name = req.headers().get("x-caller").and_then(|name| name.to_str().ok()).unwrap_or("");
+    Caller(format!("{} at {}", name, req.path()))
+}
+
+fn post(body: &str) -> test::TestRequest {
+    test::TestRequest::post()
+        .uri("/api")
+        .header("content-type", "application/json")
+        .header("x-caller", "alice")
+        .set_payload(body.to_string())
+}
+
+#[actix_rt::test]
+async fn requests() {
+    let rpc = Server::new(vec![]).with_method("echo", echo).with_method("whoami", whoami).finish();
+    let mut app = test::init_service(
+        App::new().service(web::service("/api").finish(rpc.into_web_service(caller))),
+    )
+    .await;
+
+    let res = test::call_service(
+        &mut app,
+        post(r#"{"jsonrpc": "2.0", "method": "echo", "params": [1], "id": 1}"#).to_request(),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = serde_json::from_slice(&test::read_body(res).await).unwrap();
+    assert_eq!(body, json!({"jsonrpc": "2.0", "result": [1], "id": 1}));
+
+    // notifications get an empty response
+    let res = test::call_service(
+        &mut app,
+        post(r#"{"jsonrpc": "2.0", "method": "echo", "params": [1]}"#).to_request(),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+    assert!(test::read_body(res).await.is_empty());
+
+    // the metadata closure sees the HTTP request
+    let res = test::call_service(
+        &mut app,
+        post(r#"{"jsonrpc": "2.0", "method": "whoami", "id": 2}"#).to_request(),
+    )
+    .await;
+    let body: Value = serde_json::from_slice(&test::read_body(res).await).unwrap();
+    assert_eq!(body["result"], json!("alice at /api"));
+}
@@ -0,0 +1,181 @@
+//! Client calls against scripted responses
+
+use jsonrpc_v2::client::{Client, ClientError, Transport, TransportError};
+use serde_json::{json, Value};
+
+/// Answers every request with the same response
+struct Scripted(Value);
+
+#[async_trait::async_trait]
+impl Transport for Scripted {
+    async fn send(&self, _: Vec<u8>) -> Result<Option<Vec<u8>>, TransportError> {
+        Ok(Some(serde_json::to_vec(&self.0)?))
+    }
+}
+
+fn code(res: Result<Value, ClientError>) -> i64 {
+    match res {
+        Err(ClientError::Rpc(e)) => e.code(),
+        res => panic!("expected an error response, got {:?}", res),
+    }
+}
+
+#[test]
+fn batch_null_id_error_answers_every_call() {
+    let client = Client::new(Scripted(json!([
+        {"jsonrpc": "2.0", "result": "one", "id": 1},
+        {"jsonrpc": "2.0", "error": {"code": -32700, "message": "Parse error"}, "id": null}
+    ])));
+
+    futures::executor::block_on(async {
+        let mut batch = client.batch();
+        let one = batch.request::<_, Value>("one", ()).unwrap();
+        let two = batch.request::<_, Value>("two", ()).unwrap();
+        let three = batch.request::<_, Value>("three", ()).unwrap();
+        batch.send().await.unwrap();
+
+        assert_eq!(one.await.unwrap(), json!("one"));
+        assert_eq!(code(two.await), -32700);
+        assert_eq!(code(three.await), -32700);
+    });
+}
+
+#[test]
+fn request_matches_its_own_id() {
+    // a single call answered inside a batch alongside someone else's response
+    let client = Client::new(Scripted(json!([
+        {"jsonrpc": "2.0", "result": "other", "id": 9},
+        {"jsonrpc": "2.0", "result": "mine", "id": 1}
+    ])));
+
+    let res: String = futures::executor::block_on(client.request("mine", ())).unwrap();
+    assert_eq!(res, "mine");
+}
+
+#[test]
+fn request_falls_back_to_null_id_error() {
+    let client = Client::new(Scripted(
+        json!({"jsonrpc": "2.0", "error": {"code": -32700, "message": "Parse error"}, "id": null}),
+    ));
+
+    assert_eq!(code(futures::executor::block_on(client.request("any", ()))), -32700);
+}
+
+#[test]
+fn request_without_matching_response() {
+    let client = Client::new(Scripted(json!({"jsonrpc": "2.0", "result": 1, "id": 42})));
+
+    match futures::executor::block_on(client.request::<_, Value>("any", ())) {
+        Err(ClientError::MissingResponse) => {}
+        res => panic!("expected a missing response, got {:?}", res),
+    }
+}
+
+#[test]
+fn error_round_trips() {
+    let client = Client::new(Scripted(json!({
+        "jsonrpc": "2.0",
+        "error": {"code": 17, "message": "out of stock", "data": {"sku": "a1"}},
+        "id": 1
+    })));
+
+    match futures::executor::block_on(client.request::<_, Value>("buy", ())) {
+        Err(ClientError::Rpc(e)) => {
+            assert_eq!(e.code(), 17);
+            assert_eq!(e.message(), "out of stock");
+            assert_eq!(e.data(), Some(json!({"sku": "a1"})));
+        }
+        res => panic!("expected an error response, got {:?}", res),
+    }
+}
+
+#[test]
+fn batch_matches_out_of_order_responses() {
+    let client = Client::new(Scripted(json!([
+        {"jsonrpc": "2.0", "result": "three", "id": 3},
+        {"jsonrpc": "2.0", "error": {"code": -32601, "message": "Method not found"}, "id": 2},
+        {"jsonrpc": "2.0", "result": "one", "id": 1}
+    ])));
+
+    futures::executor::block_on(async {
+        let mut batch = client.batch();
+        let one = batch.request::<_, Value>("one", ()).unwrap();
+        let two = batch.request::<_, Value>("two", ()).unwrap();
+        let three = batch.request::<_, Value>("three", ()).unwrap();
+        let four = batch.request::<_, Value>("four", ()).unwrap();
+        batch.notify("five", ()).unwrap();
+        batch.send().await.unwrap();
+
+        assert_eq!(three.await.unwrap(), json!("three"));
+        assert_eq!(one.await.unwrap(), json!("one"));
+        assert_eq!(code(two.await), -32601);
+        assert!(matches!(four.await, Err(ClientError::MissingResponse)));
+    });
+}
+
+mod loopback {
+    use super::*;
+    use jsonrpc_v2::client::LoopbackTransport;
+    use jsonrpc_v2::{Error, Params, Server};
+
+    async fn div(Params(params): Params<Vec<i64>>, _: ()) -> Result<i64, Error> {
+        match params[..] {
+            [a, b] => a.checked_div(b).ok_or(Error::Full {
+                code: 1,
+                message: "division by zero".into(),
+                data: Some(Box::new(a)),
+            }),
+            _ => Err(Error::INVALID_PARAMS),
+        }
+    }
+
+    fn client() -> Client<LoopbackTransport<()>> {
+        let server = Server::new(vec![]).with_method("div", div).finish();
+        Client::new(LoopbackTransport::new(server, ()))
+    }
+
+    #[test]
+    fn results_and_errors() {
+        let client = client();
+
+        futures::executor::block_on(async {
+            let quotient: i64 = client.request("div", [7, 2]).await.unwrap();
+            assert_eq!(quotient, 3);
+
+            match client.request::<_, i64>("div", [7, 0]).await {
+                Err(ClientError::Rpc(e)) => {
+                    assert_eq!((e.code(), e.message()), (1, "division by zero"));
+                    assert_eq!(e.data(), Some(json!(7)));
+                }
+                res => panic!("expected an error response, got {:?}", res),
+            }
+
+            assert_eq!(code(client.request("missing", ()).await), -32601);
+            assert_eq!(code(client.request("div", json!(["seven", 2])).await), -32602);
+            client.notify("div", [1, 1]).await.unwrap();
+        });
+    }
+
+    #[test]
+    fn batches() {
+        let client = client();
+
+        futures::executor::block_on(async {
+            let mut batch = client.batch();
+            let half = batch.request::<_, i64>("div", [8, 2]).unwrap();
+            let failed = batch.request::<_, Value>("div", [8, 0]).unwrap();
+            batch.notify("div", [1, 1]).unwrap();
+            let third = batch.request::<_, i64>("div", [9, 3]).unwrap();
+            batch.send().await.unwrap();
+
+            assert_eq!(half.await.unwrap(), 4);
+            assert_eq!(code(failed.await), 1);
+            assert_eq!(third.await.unwrap(), 3);
+
+            // a batch of only notifications gets no response at all
+            let mut batch = client.batch();
+            batch.notify("div", [1, 1]).unwrap();
+            batch.send().await.unwrap();
+        });
+    }
+}
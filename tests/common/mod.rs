@@ -0,0 +1,39 @@
+#![allow(dead_code)]
+
+use jsonrpc_v2::response::ResponseObjects;
+use jsonrpc_v2::{Data, Error, Params, Server};
+use serde_json::{json, Value};
+use tokio::sync::Semaphore;
+
+pub async fn call(server: &Server<()>, request: &str) -> Value {
+    match server.handle(request.as_bytes(), ()).await {
+        ResponseObjects::Empty => panic!("no response to {}", request),
+        res => serde_json::to_value(&res).unwrap(),
+    }
+}
+
+pub async fn call_method(server: &Server<()>, method: &str) -> Value {
+    call(server, &json!({"jsonrpc": "2.0", "method": method, "id": 1}).to_string()).await
+}
+
+pub async fn echo(Params(params): Params<Value>, _: ()) -> Result<Value, Error> {
+    Ok(params)
+}
+
+/// Lets a test hold handlers until it is ready for them to finish
+pub struct Gate {
+    pub started: Semaphore,
+    pub release: Semaphore,
+}
+
+impl Gate {
+    /// Called by a handler: counts it as started, then waits for the test to release it
+    pub async fn pass(&self) {
+        self.started.add_permits(1);
+        self.release.acquire().await.forget();
+    }
+}
+
+pub fn gate() -> Data<Gate> {
+    Data::new(Gate { started: Semaphore::new(0), release: Semaphore::new(0) })
+}
@@ -0,0 +1,98 @@
+//! Limits on how many handlers run at once, within a batch and across the server
+
+mod common;
+
+use common::call;
+use jsonrpc_v2::server::{BatchConcurrency, ServerBuilder};
+use jsonrpc_v2::{Data, Error, Params, Server};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How many `work` handlers are running, and the most that ever ran at once
+#[derive(Default)]
+struct Running {
+    now: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+async fn work(running: Data<Running>, Params(n): Params<u64>, _: ()) -> Result<u64, Error> {
+    let now = running.now.fetch_add(1, Ordering::SeqCst) + 1;
+    running.peak.fetch_max(now, Ordering::SeqCst);
+    tokio::time::delay_for(Duration::from_millis(5)).await;
+    running.now.fetch_sub(1, Ordering::SeqCst);
+    Ok(n)
+}
+
+fn server(
+    configure: impl FnOnce(ServerBuilder<()>) -> ServerBuilder<()>,
+) -> (Arc<Server<()>>, Data<Running>) {
+    let running = Data::new(Running::default());
+    let builder = Server::new(vec![]).with_data(running.clone()).with_method("work", work);
+    (configure(builder).finish(), running)
+}
+
+fn batch(items: u64) -> String {
+    let items = (0..items)
+        .map(|n| json!({"jsonrpc": "2.0", "method": "work", "params": n, "id": n}))
+        .collect::<Vec<_>>();
+    Value::from(items).to_string()
+}
+
+fn ids(response: &Value) -> Vec<u64> {
+    response.as_array().unwrap().iter().map(|item| item["id"].as_u64().unwrap()).collect()
+}
+
+#[tokio::test]
+async fn unbounded_batch() {
+    let (server, running) = server(|builder| builder);
+
+    let response = call(&server, &batch(6)).await;
+    assert_eq!(response.as_array().unwrap().len(), 6);
+    assert_eq!(running.peak.load(Ordering::SeqCst), 6);
+}
+
+#[tokio::test]
+async fn limited_batch() {
+    let (server, running) =
+        server(|builder| builder.with_batch_concurrency(BatchConcurrency::Limit(2)));
+
+    let response = call(&server, &batch(6)).await;
+    assert_eq!(response.as_array().unwrap().len(), 6);
+    assert_eq!(running.peak.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn sequential_batch() {
+    let (server, running) =
+        server(|builder| builder.with_batch_concurrency(BatchConcurrency::Sequential));
+
+    let response = call(&server, &batch(4)).await;
+    // one at a time, so they also complete in order
+    assert_eq!(ids(&response), vec![0, 1, 2, 3]);
+    assert_eq!(running.peak.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn max_concurrency_across_requests() {
+    let (server, running) = server(|builder| builder.with_max_concurrency(2));
+
+    let requests = (0..6).map(|n| {
+        let request = json!({"jsonrpc": "2.0", "method": "work", "params": n, "id": n}).to_string();
+        let server = &server;
+        async move { call(server, &request).await }
+    });
+    let responses = futures::future::join_all(requests).await;
+
+    for (n, response) in responses.iter().enumerate() {
+        assert_eq!(response["result"], json!(n));
+    }
+    assert_eq!(running.peak.load(Ordering::SeqCst), 2);
+
+    // batch items count against the same limit
+    running.peak.store(0, Ordering::SeqCst);
+    let response = call(&server, &batch(6)).await;
+    assert_eq!(response.as_array().unwrap().len(), 6);
+    assert_eq!(running.peak.load(Ordering::SeqCst), 2);
+}
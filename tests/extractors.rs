@@ -0,0 +1,171 @@
+//! The built-in `FromRequest` extractors and custom ones
+
+mod common;
+
+use common::{call, call_method};
+use jsonrpc_v2::request::{FromRequest, ParamsSchema, RequestObject};
+use jsonrpc_v2::{Data, Error, Id, Method, Params, RawParams, Server};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+async fn id(id: Id, _: ()) -> Result<String, Error> {
+    Ok(serde_json::to_string(&id).unwrap())
+}
+
+async fn raw_params(params: RawParams, _: ()) -> Result<String, Error> {
+    Ok(params.get().to_string())
+}
+
+async fn need(number: Data<u32>, _: ()) -> Result<u32, Error> {
+    Ok(*number)
+}
+
+/// Custom extractor that does not read the params
+struct MethodLength(usize);
+
+#[async_trait::async_trait]
+impl FromRequest for MethodLength {
+    async fn from_request(req: &RequestObject, _: ()) -> Result<Self, Error> {
+        Ok(MethodLength(req.method.len()))
+    }
+}
+
+impl ParamsSchema for MethodLength {}
+
+async fn method_length(MethodLength(len): MethodLength, _: ()) -> Result<usize, Error> {
+    Ok(len)
+}
+
+async fn five(
+    Params(numbers): Params<Vec<u32>>,
+    id: Id,
+    Method(method): Method,
+    raw: RawParams,
+    MethodLength(len): MethodLength,
+    _: (),
+) -> Result<Value, Error> {
+    Ok(json!([numbers.iter().sum::<u32>(), id.to_string(), method, raw.get(), len]))
+}
+
+/// Extractor that always fails with `CODE`
+struct Failing<const CODE: i64>;
+
+#[async_trait::async_trait]
+impl<const CODE: i64> FromRequest for Failing<CODE> {
+    async fn from_request(_: &RequestObject, _: ()) -> Result<Self, Error> {
+        Err(Error::Provided { code: CODE, message: "failing" })
+    }
+}
+
+impl<const CODE: i64> ParamsSchema for Failing<CODE> {}
+
+async fn guarded(
+    calls: Data<AtomicUsize>,
+    _: Failing<1>,
+    _: Failing<2>,
+    _: (),
+) -> Result<(), Error> {
+    calls.fetch_add(1, Ordering::SeqCst);
+    Ok(())
+}
+
+async fn guarded_params(
+    calls: Data<AtomicUsize>,
+    _: Params<u32>,
+    _: Failing<2>,
+    _: (),
+) -> Result<(), Error> {
+    calls.fetch_add(1, Ordering::SeqCst);
+    Ok(())
+}
+
+fn server() -> Arc<Server<()>> {
+    server_with_calls(Data::new(AtomicUsize::new(0)))
+}
+
+fn server_with_calls(calls: Data<AtomicUsize>) -> Arc<Server<()>> {
+    Server::new(vec![])
+        .with_data(calls)
+        .with_method("five", five)
+        .with_method("guarded", guarded)
+        .with_method("guarded_params", guarded_params)
+        .with_method("id", id)
+        .with_method("raw_params", raw_params)
+        .with_method("method_length", method_length)
+        .with_method("need", need)
+        .finish()
+}
+
+#[test]
+fn id_as_sent() {
+    let server = server();
+
+    futures::executor::block_on(async {
+        for id in &[r#""abc""#, "7", "-7", "18446744073709551616", "1e2", "1.50"] {
+            let request = format!(r#"{{"jsonrpc": "2.0", "method": "id", "id": {}}}"#, id);
+            assert_eq!(call(&server, &request).await["result"], json!(id), "id {}", id);
+        }
+    });
+}
+
+#[test]
+fn raw_params_unchanged() {
+    let server = server();
+
+    futures::executor::block_on(async {
+        let params = r#"{"b": 1e2,  "a": [1, 18446744073709551616, "A"]}"#;
+        let request = format!(
+            r#"{{"jsonrpc": "2.0", "method": "raw_params", "params": {}, "id": 1}}"#,
+            params
+        );
+        assert_eq!(call(&server, &request).await["result"], json!(params));
+
+        // missing params read as `null`
+        let request = r#"{"jsonrpc": "2.0", "method": "raw_params", "id": 1}"#;
+        assert_eq!(call(&server, request).await["result"], json!("null"));
+    });
+}
+
+#[test]
+fn custom_extractor() {
+    let request = r#"{"jsonrpc": "2.0", "method": "method_length", "id": 1}"#;
+    let res = futures::executor::block_on(call(&server(), request));
+    assert_eq!(res["result"], json!(13));
+}
+
+#[test]
+fn unregistered_data() {
+    let res = futures::executor::block_on(call_method(&server(), "need"));
+    assert_eq!(
+        res["error"],
+        json!({
+            "code": -32603,
+            "message": "Internal Error",
+            "data": "Data<u32> was not registered with `ServerBuilder::with_data`"
+        })
+    );
+}
+
+#[test]
+fn five_extractors() {
+    let request = r#"{"jsonrpc": "2.0", "method": "five", "params": [1, 2], "id": "x"}"#;
+    let res = futures::executor::block_on(call(&server(), request));
+    assert_eq!(res["result"], json!([3, "x", "five", "[1, 2]", 4]));
+}
+
+#[test]
+fn first_failing_extractor_wins() {
+    let calls = Data::new(AtomicUsize::new(0));
+    let server = server_with_calls(calls.clone());
+
+    futures::executor::block_on(async {
+        assert_eq!(call_method(&server, "guarded").await["error"]["code"], json!(1));
+
+        let request = r#"{"jsonrpc": "2.0", "method": "guarded_params", "params": "x", "id": 1}"#;
+        assert_eq!(call(&server, request).await["error"]["code"], json!(-32602));
+    });
+
+    // an extractor error stops the handler from running
+    assert_eq!(calls.load(Ordering::SeqCst), 0);
+}
@@ -0,0 +1,70 @@
+//! Handling unregistered methods with `ServerBuilder::with_fallback`
+
+mod common;
+
+use common::call_method;
+use jsonrpc_v2::middleware::{Middleware, Next};
+use jsonrpc_v2::request::RequestObject;
+use jsonrpc_v2::{BoxedSerialize, Error, Method, Server};
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+
+async fn known(_: ()) -> Result<&'static str, Error> {
+    Ok("known")
+}
+
+async fn unknown(Method(method): Method, _: ()) -> Result<String, Error> {
+    Ok(format!("fallback for {}", method))
+}
+
+/// Records the method of every request it sees
+struct Log(Arc<Mutex<Vec<String>>>);
+
+#[async_trait::async_trait]
+impl Middleware<()> for Log {
+    async fn handle(
+        &self,
+        req: RequestObject,
+        metadata: (),
+        next: Next<'_, '_, ()>,
+    ) -> Result<BoxedSerialize, Error> {
+        self.0.lock().unwrap().push(req.method.to_string());
+        next.run(req, metadata).await
+    }
+}
+
+#[test]
+fn unknown_methods() {
+    let log = Arc::new(Mutex::new(vec![]));
+    let server = Server::new(vec![Arc::new(Log(Arc::clone(&log)))])
+        .with_method("known", known)
+        .with_fallback(unknown)
+        .finish();
+
+    futures::executor::block_on(async {
+        assert_eq!(call_method(&server, "known").await["result"], json!("known"));
+        assert_eq!(call_method(&server, "missing").await["result"], json!("fallback for missing"));
+    });
+
+    // the fallback runs through the server middlewares too
+    assert_eq!(*log.lock().unwrap(), vec!["known", "missing"]);
+}
+
+#[test]
+fn unregistered_methods_fall_through() {
+    let server = Server::new(vec![]).with_method("known", known).with_fallback(unknown).finish();
+    let registry = server.registry();
+
+    futures::executor::block_on(async {
+        assert!(registry.unregister("known"));
+        assert_eq!(call_method(&server, "known").await["result"], json!("fallback for known"));
+    });
+}
+
+#[test]
+fn method_not_found_without_fallback() {
+    let server = Server::new(vec![]).with_method("known", known).finish();
+
+    let res = futures::executor::block_on(call_method(&server, "missing"));
+    assert_eq!(res["error"]["code"], json!(-32601));
+}
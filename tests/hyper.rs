@@ -0,0 +1,65 @@
+//! Serving a `Server` as a `hyper` service
+
+#![cfg(feature = "hyper-integration")]
+
+mod common;
+
+use common::echo;
+use hyper::{header, Body, Method, Request, StatusCode};
+use jsonrpc_v2::Server;
+use serde_json::{json, Value};
+use tower_service::Service;
+
+fn request(method: Method, body: &str) -> Request<Body> {
+    Request::builder().method(method).uri("/").body(Body::from(body.to_string())).unwrap()
+}
+
+async fn json_body(res: hyper::Response<Body>) -> Value {
+    serde_json::from_slice(&hyper::body::to_bytes(res.into_body()).await.unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn requests() {
+    let rpc = Server::new(vec![]).with_max_payload_bytes(128).with_method("echo", echo).finish();
+    let mut service = rpc.into_hyper_web_service(|_| ());
+
+    let res = service
+        .call(request(
+            Method::POST,
+            r#"{"jsonrpc": "2.0", "method": "echo", "params": [1], "id": 1}"#,
+        ))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()[header::CONTENT_TYPE], "application/json");
+    assert_eq!(json_body(res).await, json!({"jsonrpc": "2.0", "result": [1], "id": 1}));
+
+    // notifications get an empty response
+    let res = service
+        .call(request(Method::POST, r#"{"jsonrpc": "2.0", "method": "echo", "params": [1]}"#))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+    assert!(hyper::body::to_bytes(res.into_body()).await.unwrap().is_empty());
+
+    let res = service.call(request(Method::GET, "")).await.unwrap();
+    assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(res.headers()[header::ALLOW], "POST");
+
+    let params = "x".repeat(256);
+    let body = json!({"jsonrpc": "2.0", "method": "echo", "params": [params], "id": 2}).to_string();
+    let res = service.call(request(Method::POST, &body)).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        json_body(res).await,
+        json!({
+            "jsonrpc": "2.0",
+            "error": {
+                "code": -32600,
+                "message": "Invalid Request",
+                "data": "payload exceeds the limit of 128 bytes"
+            },
+            "id": null
+        })
+    );
+}
@@ -0,0 +1,45 @@
+//! Deserializing `Id` outside of the server's own request parsing
+
+use jsonrpc_v2::Id;
+use serde::Deserialize;
+use serde_json::json;
+
+#[test]
+fn from_value() {
+    assert_eq!(serde_json::from_value::<Id>(json!(7)).unwrap(), Id::Num(7));
+    assert_eq!(serde_json::from_value::<Id>(json!(-7)).unwrap(), Id::Num(-7));
+    assert_eq!(serde_json::from_value::<Id>(json!("a")).unwrap(), Id::from("a"));
+    assert_eq!(serde_json::from_value::<Id>(json!(null)).unwrap(), Id::Null);
+    assert_eq!(serde_json::from_value::<Id>(json!(u64::MAX)).unwrap(), Id::from(u64::MAX));
+    assert_eq!(serde_json::from_value::<Id>(json!(1.5)).unwrap().to_string(), "1.5");
+    assert!(serde_json::from_value::<Id>(json!([1])).is_err());
+    assert!(serde_json::from_value::<Id>(json!({"id": 1})).is_err());
+}
+
+#[test]
+fn untagged_and_flattened() {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Message {
+        Call { id: Id, method: String },
+        Reply { id: Id },
+    }
+
+    #[derive(Deserialize)]
+    struct Envelope {
+        #[serde(flatten)]
+        message: Message,
+    }
+
+    match serde_json::from_str(r#"{"id": "x", "method": "m"}"#).unwrap() {
+        Envelope { message: Message::Call { id, method } } => {
+            assert_eq!((id, method.as_str()), (Id::from("x"), "m"));
+        }
+        _ => panic!("expected a call"),
+    }
+
+    match serde_json::from_str(r#"{"id": 18446744073709551615}"#).unwrap() {
+        Message::Reply { id } => assert_eq!(id.to_string(), "18446744073709551615"),
+        _ => panic!("expected a reply"),
+    }
+}
@@ -0,0 +1,74 @@
+//! Batch length and nesting depth limits
+
+mod common;
+
+use common::{call, echo};
+use jsonrpc_v2::Server;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+fn server() -> Arc<Server<()>> {
+    Server::new(vec![]).with_max_batch_items(2).with_max_depth(3).with_method("echo", echo).finish()
+}
+
+fn invalid_request(data: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "error": {"code": -32600, "message": "Invalid Request", "data": data},
+        "id": null
+    })
+}
+
+fn echo_request(id: u64) -> Value {
+    json!({"jsonrpc": "2.0", "method": "echo", "params": [id], "id": id})
+}
+
+#[test]
+fn batch_items() {
+    let server = server();
+
+    futures::executor::block_on(async {
+        let at_limit = json!([echo_request(1), echo_request(2)]).to_string();
+        assert_eq!(call(&server, &at_limit).await.as_array().unwrap().len(), 2);
+
+        let over_limit = json!([echo_request(1), echo_request(2), echo_request(3)]).to_string();
+        assert_eq!(
+            call(&server, &over_limit).await,
+            invalid_request("batch of 3 items exceeds the limit of 2 items")
+        );
+    });
+}
+
+#[test]
+fn depth() {
+    let server = server();
+
+    futures::executor::block_on(async {
+        // the request object, its params and one array inside them
+        let at_limit = r#"{"jsonrpc": "2.0", "method": "echo", "params": [[1]], "id": 1}"#;
+        assert_eq!(call(&server, at_limit).await["result"], json!([[1]]));
+
+        let too_deep = r#"{"jsonrpc": "2.0", "method": "echo", "params": [[{}]], "id": 1}"#;
+        assert_eq!(
+            call(&server, too_deep).await,
+            invalid_request("JSON nesting exceeds the depth limit of 3")
+        );
+
+        // the batch array counts as a level too
+        let batch = r#"[{"jsonrpc": "2.0", "method": "echo", "params": [[1]], "id": 1}]"#;
+        assert_eq!(
+            call(&server, batch).await,
+            invalid_request("JSON nesting exceeds the depth limit of 3")
+        );
+    });
+}
+
+#[test]
+fn brackets_in_strings() {
+    let server = server();
+    let request =
+        r#"{"jsonrpc": "2.0", "method": "echo", "params": ["[[{{", "\"[[", "\\"], "id": 1}"#;
+
+    let res = futures::executor::block_on(call(&server, request));
+    assert_eq!(res["result"], json!(["[[{{", "\"[[", "\\"]));
+}
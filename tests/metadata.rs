@@ -0,0 +1,32 @@
+//! Metadata that is `Send` but not `Sync`, passed to extractors and handlers
+
+use jsonrpc_v2::response::ResponseObjects;
+use jsonrpc_v2::{Error, Meta, Metadata, Server};
+use serde_json::json;
+use std::cell::Cell;
+
+#[derive(Clone)]
+struct Peer(Cell<u32>);
+
+impl Metadata for Peer {}
+
+async fn peer(Meta(from_extractor): Meta<Peer>, metadata: Peer) -> Result<u32, Error> {
+    from_extractor.0.set(from_extractor.0.get() + 1);
+    Ok(from_extractor.0.get() + metadata.0.get())
+}
+
+#[test]
+fn extractors_get_their_own_clone() {
+    let server = Server::new(vec![]).with_method("peer", peer).finish();
+    let request = r#"{"jsonrpc": "2.0", "method": "peer", "id": 1}"#;
+
+    match futures::executor::block_on(server.handle(request.as_bytes(), Peer(Cell::new(20)))) {
+        ResponseObjects::One(res) => {
+            assert_eq!(
+                serde_json::to_value(&res).unwrap(),
+                json!({"jsonrpc": "2.0", "result": 41, "id": 1})
+            )
+        }
+        _ => panic!("expected a single response"),
+    }
+}
@@ -0,0 +1,214 @@
+//! Builders mounted under a prefix with `ServerBuilder::with_mount`
+
+mod common;
+
+use common::call_method;
+use jsonrpc_v2::middleware::{Middleware, Next};
+use jsonrpc_v2::request::RequestObject;
+use jsonrpc_v2::router::{FastRouter, MapRouter, Route, Router};
+use jsonrpc_v2::{BoxedSerialize, Data, Error, Server};
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+async fn ok(_: ()) -> Result<&'static str, Error> {
+    Ok("ok")
+}
+
+async fn need(number: Data<u32>, _: ()) -> Result<u32, Error> {
+    Ok(*number)
+}
+
+async fn hang(_: ()) -> Result<(), Error> {
+    futures::future::pending().await
+}
+
+/// Records its name in the shared log before passing the request on
+struct Tag(&'static str, Arc<Mutex<Vec<&'static str>>>);
+
+#[async_trait::async_trait]
+impl Middleware<()> for Tag {
+    async fn handle(
+        &self,
+        req: RequestObject,
+        metadata: (),
+        next: Next<'_, '_, ()>,
+    ) -> Result<BoxedSerialize, Error> {
+        self.1.lock().unwrap().push(self.0);
+        next.run(req, metadata).await
+    }
+}
+
+/// Router that keeps the default `Router::iter`, so its routes cannot be listed
+#[derive(Clone, Default)]
+struct Unlisted(MapRouter<()>);
+
+impl Router<()> for Unlisted {
+    fn get(&self, name: &str) -> Option<&Route<()>> {
+        self.0.get(name)
+    }
+    fn insert(&mut self, name: String, route: Route<()>) -> Option<Route<()>> {
+        self.0.insert(name, route)
+    }
+}
+
+#[test]
+fn prefixed_names() {
+    let server = Server::new(vec![])
+        .with_method("ok", ok)
+        .with_mount("admin.", Server::new(vec![]).with_method("ok", ok))
+        .finish();
+
+    futures::executor::block_on(async {
+        assert_eq!(call_method(&server, "ok").await["result"], json!("ok"));
+        assert_eq!(call_method(&server, "admin.ok").await["result"], json!("ok"));
+        assert_eq!(call_method(&server, "admin.missing").await["error"]["code"], json!(-32601));
+    });
+}
+
+#[test]
+fn middleware_order() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let parent: Vec<Arc<dyn Middleware<()>>> = vec![Arc::new(Tag("parent", Arc::clone(&log)))];
+    let mounted: Vec<Arc<dyn Middleware<()>>> = vec![Arc::new(Tag("mounted", Arc::clone(&log)))];
+
+    let server = Server::new(parent)
+        .with_mount(
+            "sub.",
+            Server::new(mounted).with_method_middleware(
+                "ok",
+                ok,
+                vec![Arc::new(Tag("method", Arc::clone(&log)))],
+            ),
+        )
+        .finish();
+
+    futures::executor::block_on(call_method(&server, "sub.ok"));
+    assert_eq!(*log.lock().unwrap(), vec!["parent", "mounted", "method"]);
+}
+
+#[test]
+fn timeout_inheritance() {
+    let server = Server::new(vec![])
+        .with_timeout(Duration::from_secs(60))
+        .with_mount(
+            "short.",
+            Server::new(vec![])
+                .with_timeout(Duration::from_millis(10))
+                .with_method("hang", hang)
+                .with_method_timeout("hang_briefly", hang, Duration::from_millis(1)),
+        )
+        .finish();
+
+    futures::executor::block_on(async {
+        // the mounted builder's default and per-method timeouts apply over the parent's
+        assert_eq!(call_method(&server, "short.hang").await["error"]["code"], json!(-32001));
+        assert_eq!(
+            call_method(&server, "short.hang_briefly").await["error"]["code"],
+            json!(-32001)
+        );
+    });
+
+    // without a timeout of its own, a mounted method gets the parent's default
+    let server = Server::new(vec![])
+        .with_timeout(Duration::from_millis(10))
+        .with_mount("inherit.", Server::new(vec![]).with_method("hang", hang))
+        .finish();
+    let res = futures::executor::block_on(call_method(&server, "inherit.hang"));
+    assert_eq!(res["error"]["code"], json!(-32001));
+}
+
+#[test]
+fn mounted_data() {
+    let server = Server::new(vec![])
+        .with_data(Data::new("parent"))
+        .with_mount("m.", Server::new(vec![]).with_data(Data::new(7u32)).with_method("need", need))
+        .with_method("need", need)
+        .finish();
+
+    futures::executor::block_on(async {
+        assert_eq!(call_method(&server, "m.need").await["result"], json!(7));
+        // shared with the whole server, like data registered on the parent
+        assert_eq!(call_method(&server, "need").await["result"], json!(7));
+    });
+}
+
+#[test]
+#[should_panic(expected = "cannot mount under `m.`: `Data<u32>` is registered on both builders")]
+fn mounted_data_collision() {
+    Server::new(vec![])
+        .with_data(Data::new(1u32))
+        .with_mount("m.", Server::new(vec![]).with_data(Data::new(7u32)).with_method("need", need));
+}
+
+#[test]
+#[should_panic(
+    expected = "cannot mount under `m.`: `with_fallback` only applies to the whole server"
+)]
+fn mounted_fallback() {
+    Server::new(vec![])
+        .with_mount("m.", Server::new(vec![]).with_method("ok", ok).with_fallback(ok));
+}
+
+#[test]
+#[should_panic(
+    expected = "cannot mount under `m.`: `with_strict` only applies to the whole server"
+)]
+fn mounted_server_settings() {
+    Server::new(vec![])
+        .with_mount("m.", Server::new(vec![]).with_strict(true).with_method("ok", ok));
+}
+
+#[test]
+#[should_panic(expected = "cannot mount `admin.ok`: `admin.ok` is already registered")]
+fn mount_collision() {
+    Server::new(vec![])
+        .with_method("admin.ok", ok)
+        .with_mount("admin.", Server::new(vec![]).with_method("ok", ok));
+}
+
+#[test]
+#[should_panic(
+    expected = "cannot register `admin.ok`: mounted method `admin.ok` is already registered"
+)]
+fn register_after_mount_collision() {
+    Server::new(vec![])
+        .with_mount("admin.", Server::new(vec![]).with_method("ok", ok))
+        .with_method("admin.ok", ok);
+}
+
+#[test]
+#[should_panic(expected = "cannot mount `eth_call`: `eth_*` is already registered")]
+fn mount_under_wildcard() {
+    Server::with_router(FastRouter::default(), vec![])
+        .with_method("eth_*", ok)
+        .with_mount("eth_", Server::new(vec![]).with_method("call", ok));
+}
+
+#[test]
+#[should_panic(
+    expected = "cannot register `eth_*`: mounted method `eth_call` is already registered"
+)]
+fn wildcard_over_mount() {
+    Server::with_router(FastRouter::default(), vec![])
+        .with_mount("eth_", Server::new(vec![]).with_method("call", ok))
+        .with_method("eth_*", ok);
+}
+
+#[test]
+#[should_panic(
+    expected = "cannot mount under `m.`: the router of the mounted builder does not implement `Router::iter`"
+)]
+fn mount_unlisted_router() {
+    Server::new(vec![])
+        .with_mount("m.", Server::with_router(Unlisted::default(), vec![]).with_method("ok", ok));
+}
+
+#[test]
+#[should_panic(
+    expected = "cannot mount under `m.`: the router of this builder does not implement `Router::iter`"
+)]
+fn mount_into_unlisted_router() {
+    Server::with_router(Unlisted::default(), vec![])
+        .with_mount("m.", Server::new(vec![]).with_method("ok", ok));
+}
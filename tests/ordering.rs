@@ -0,0 +1,55 @@
+//! Batch responses in request order with `with_ordered_batches`
+
+mod common;
+
+use common::call;
+use jsonrpc_v2::{Data, Error, Server};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+/// Only finishes once `second` has
+async fn first(second_done: Data<Semaphore>, _: ()) -> Result<&'static str, Error> {
+    second_done.acquire().await.forget();
+    Ok("first")
+}
+
+async fn second(second_done: Data<Semaphore>, _: ()) -> Result<&'static str, Error> {
+    second_done.add_permits(1);
+    Ok("second")
+}
+
+fn server(ordered: bool) -> Arc<Server<()>> {
+    Server::new(vec![])
+        .with_data(Data::new(Semaphore::new(0)))
+        .with_ordered_batches(ordered)
+        .with_method("first", first)
+        .with_method("second", second)
+        .finish()
+}
+
+const BATCH: &str = r#"[
+    {"jsonrpc": "2.0", "method": "first", "id": 1},
+    {"jsonrpc": "2.0", "method": 5, "id": 2},
+    {"jsonrpc": "2.0", "method": "second", "id": 3}
+]"#;
+
+fn ids(response: &Value) -> Vec<u64> {
+    response.as_array().unwrap().iter().map(|item| item["id"].as_u64().unwrap()).collect()
+}
+
+#[tokio::test]
+async fn request_order() {
+    let response = call(&server(true), BATCH).await;
+
+    assert_eq!(ids(&response), vec![1, 2, 3]);
+    assert_eq!(response[0]["result"], json!("first"));
+    assert_eq!(response[1]["error"]["code"], json!(-32600));
+    assert_eq!(response[2]["result"], json!("second"));
+}
+
+#[tokio::test]
+async fn completion_order_by_default() {
+    // invalid items go after the responses of the calls, which come as they complete
+    assert_eq!(ids(&call(&server(false), BATCH).await), vec![3, 1, 2]);
+}
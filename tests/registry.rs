@@ -0,0 +1,111 @@
+//! Changing the methods of a running server through its `Registry`
+
+mod common;
+
+use common::{call_method, gate, Gate};
+use jsonrpc_v2::router::{Route, Router};
+use jsonrpc_v2::{Data, Error, Server};
+use serde_json::json;
+use std::sync::Arc;
+
+async fn one(_: ()) -> Result<u32, Error> {
+    Ok(1)
+}
+
+async fn two(_: ()) -> Result<u32, Error> {
+    Ok(2)
+}
+
+async fn gated_one(gate: Data<Gate>, _: ()) -> Result<u32, Error> {
+    gate.pass().await;
+    Ok(1)
+}
+
+#[test]
+fn register_replace_unregister() {
+    let server = Server::new(vec![]).finish();
+    let registry = server.registry();
+
+    futures::executor::block_on(async {
+        assert_eq!(call_method(&server, "n").await["error"]["code"], json!(-32601));
+
+        assert!(!registry.register("n", one));
+        assert!(registry.contains("n"));
+        assert_eq!(call_method(&server, "n").await["result"], json!(1));
+
+        assert!(registry.register("n", two));
+        assert_eq!(call_method(&server, "n").await["result"], json!(2));
+
+        assert!(registry.unregister("n"));
+        assert!(!registry.contains("n"));
+        assert!(!registry.unregister("n"));
+        assert_eq!(call_method(&server, "n").await["error"]["code"], json!(-32601));
+    });
+}
+
+#[tokio::test]
+async fn replace_while_in_flight() {
+    let gate = gate();
+    let server = Server::new(vec![]).with_data(gate.clone()).with_method("n", gated_one).finish();
+    let registry = server.registry();
+
+    let in_flight = tokio::spawn({
+        let server = Arc::clone(&server);
+        async move { call_method(&server, "n").await }
+    });
+    gate.started.acquire().await.forget();
+
+    // the running call keeps the handler it was dispatched to, later calls get the new one
+    assert!(registry.register("n", two));
+    assert_eq!(call_method(&server, "n").await["result"], json!(2));
+
+    gate.release.add_permits(1);
+    assert_eq!(in_flight.await.unwrap()["result"], json!(1));
+}
+
+#[test]
+fn concurrent_registrations() {
+    let server = Server::new(vec![]).finish();
+
+    let threads = (0..8)
+        .map(|thread| {
+            let registry = server.registry();
+            std::thread::spawn(move || {
+                for n in 0..50 {
+                    registry.register(format!("m{}_{}", thread, n), one);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    threads.into_iter().for_each(|thread| thread.join().unwrap());
+
+    let registry = server.registry();
+    for thread in 0..8 {
+        for n in 0..50 {
+            assert!(registry.contains(&format!("m{}_{}", thread, n)));
+        }
+    }
+}
+
+/// Router that can only grow
+#[derive(Clone, Default)]
+struct AppendOnly(Vec<(String, Route<()>)>);
+
+impl Router<()> for AppendOnly {
+    fn get(&self, name: &str) -> Option<&Route<()>> {
+        self.0.iter().rev().find(|(n, _)| n == name).map(|(_, route)| route)
+    }
+    fn insert(&mut self, name: String, route: Route<()>) -> Option<Route<()>> {
+        self.0.push((name, route));
+        None
+    }
+}
+
+#[test]
+fn router_without_remove() {
+    let server = Server::with_router(AppendOnly::default(), vec![]).with_method("n", one).finish();
+    let registry = server.registry();
+
+    assert!(!registry.unregister("n"));
+    assert_eq!(futures::executor::block_on(call_method(&server, "n"))["result"], json!(1));
+}
@@ -0,0 +1,68 @@
+//! Method lookup with `FastRouter` patterns
+
+mod common;
+
+use common::call_method;
+use jsonrpc_v2::router::FastRouter;
+use jsonrpc_v2::{Error, Method, Server};
+use serde_json::{json, Value};
+
+async fn exact(_: ()) -> Result<&'static str, Error> {
+    Ok("exact")
+}
+
+async fn short(Method(method): Method, _: ()) -> Result<String, Error> {
+    Ok(format!("short {}", method))
+}
+
+async fn long(Method(method): Method, _: ()) -> Result<String, Error> {
+    Ok(format!("long {}", method))
+}
+
+fn result(server: &Server<()>, method: &str) -> Value {
+    futures::executor::block_on(call_method(server, method))["result"].clone()
+}
+
+#[test]
+fn exact_before_wildcard() {
+    let server = Server::with_router(FastRouter::default(), vec![])
+        .with_method("debug_*", short)
+        .with_method("debug_trace", exact)
+        .finish();
+
+    assert_eq!(result(&server, "debug_trace"), json!("exact"));
+    assert_eq!(result(&server, "debug_traceCall"), json!("short debug_traceCall"));
+    assert_eq!(result(&server, "debug_"), json!("short debug_"));
+    // not found, so there is no result
+    assert_eq!(result(&server, "debug"), Value::Null);
+}
+
+#[test]
+fn longest_prefix_first() {
+    // registered shortest first, to show the order of registration does not matter
+    let server = Server::with_router(FastRouter::default(), vec![])
+        .with_method("*", short)
+        .with_method("debug_*", short)
+        .with_method("debug_trace*", long)
+        .finish();
+
+    assert_eq!(result(&server, "debug_traceBlock"), json!("long debug_traceBlock"));
+    assert_eq!(result(&server, "debug_getRawBlock"), json!("short debug_getRawBlock"));
+    assert_eq!(result(&server, "eth_call"), json!("short eth_call"));
+}
+
+#[test]
+fn replace_and_remove_patterns() {
+    let server = Server::with_router(FastRouter::default(), vec![])
+        .with_method("debug_*", short)
+        .with_method("debug_trace*", short)
+        .finish();
+    let registry = server.registry();
+
+    assert!(registry.register("debug_trace*", long));
+    assert_eq!(result(&server, "debug_traceBlock"), json!("long debug_traceBlock"));
+
+    assert!(registry.unregister("debug_trace*"));
+    assert_eq!(result(&server, "debug_traceBlock"), json!("short debug_traceBlock"));
+    assert!(!registry.unregister("debug_trace*"));
+}
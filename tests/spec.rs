@@ -0,0 +1,308 @@
+//! Examples from the JSON-RPC 2.0 specification (https://www.jsonrpc.org/specification#examples),
+//! run against a server in strict mode
+
+use jsonrpc_v2::response::ResponseObjects;
+use jsonrpc_v2::{Error, Params, Server};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SubtractParams {
+    ByPosition(i64, i64),
+    ByName { minuend: i64, subtrahend: i64 },
+}
+
+impl paperclip::v2::schema::Apiv2Schema for SubtractParams {}
+
+async fn subtract(Params(params): Params<SubtractParams>, _: ()) -> Result<i64, Error> {
+    Ok(match params {
+        SubtractParams::ByPosition(minuend, subtrahend) => minuend - subtrahend,
+        SubtractParams::ByName { minuend, subtrahend } => minuend - subtrahend,
+    })
+}
+
+async fn sum(Params(params): Params<Vec<i64>>, _: ()) -> Result<i64, Error> {
+    Ok(params.iter().sum())
+}
+
+async fn ignore(Params(_): Params<Value>, _: ()) -> Result<Value, Error> {
+    Ok(Value::Null)
+}
+
+async fn get_data(Params(_): Params<Value>, _: ()) -> Result<Value, Error> {
+    Ok(json!(["hello", 5]))
+}
+
+fn server(strict: bool) -> Arc<Server<()>> {
+    Server::new(vec![])
+        .with_strict(strict)
+        .with_method("subtract", subtract)
+        .with_method("sum", sum)
+        .with_method("update", ignore)
+        .with_method("notify_hello", ignore)
+        .with_method("notify_sum", ignore)
+        .with_method("get_data", get_data)
+        .finish()
+}
+
+/// Handle `request` on a strict server
+fn call(request: &str) -> Option<Value> {
+    handle(&server(true), request)
+}
+
+/// Handle `request`, returning the response as JSON, or `None` for no response
+fn handle(server: &Server<()>, request: &str) -> Option<Value> {
+    match futures::executor::block_on(server.handle(request.as_bytes(), ())) {
+        ResponseObjects::Empty => None,
+        res => {
+            let mut res = serde_json::to_value(&res).unwrap();
+            // batch responses may come back in any order
+            if let Value::Array(items) = &mut res {
+                items.sort_by_key(|item| item["id"].to_string());
+            }
+            Some(res)
+        }
+    }
+}
+
+fn sorted(mut batch: Value) -> Value {
+    batch.as_array_mut().unwrap().sort_by_key(|item| item["id"].to_string());
+    batch
+}
+
+#[test]
+fn positional_parameters() {
+    assert_eq!(
+        call(r#"{"jsonrpc": "2.0", "method": "subtract", "params": [42, 23], "id": 1}"#),
+        Some(json!({"jsonrpc": "2.0", "result": 19, "id": 1}))
+    );
+    assert_eq!(
+        call(r#"{"jsonrpc": "2.0", "method": "subtract", "params": [23, 42], "id": 2}"#),
+        Some(json!({"jsonrpc": "2.0", "result": -19, "id": 2}))
+    );
+}
+
+#[test]
+fn named_parameters() {
+    assert_eq!(
+        call(
+            r#"{"jsonrpc": "2.0", "method": "subtract", "params": {"subtrahend": 23, "minuend": 42}, "id": 3}"#
+        ),
+        Some(json!({"jsonrpc": "2.0", "result": 19, "id": 3}))
+    );
+    assert_eq!(
+        call(
+            r#"{"jsonrpc": "2.0", "method": "subtract", "params": {"minuend": 42, "subtrahend": 23}, "id": 4}"#
+        ),
+        Some(json!({"jsonrpc": "2.0", "result": 19, "id": 4}))
+    );
+}
+
+#[test]
+fn notification() {
+    assert_eq!(call(r#"{"jsonrpc": "2.0", "method": "update", "params": [1,2,3,4,5]}"#), None);
+    assert_eq!(call(r#"{"jsonrpc": "2.0", "method": "foobar"}"#), None);
+}
+
+#[test]
+fn non_existent_method() {
+    assert_eq!(
+        call(r#"{"jsonrpc": "2.0", "method": "foobar", "id": "1"}"#),
+        Some(
+            json!({"jsonrpc": "2.0", "error": {"code": -32601, "message": "Method not found"}, "id": "1"})
+        )
+    );
+}
+
+#[test]
+fn invalid_json() {
+    assert_eq!(
+        call(r#"{"jsonrpc": "2.0", "method": "foobar, "params": "bar", "baz]"#),
+        Some(
+            json!({"jsonrpc": "2.0", "error": {"code": -32700, "message": "Parse error"}, "id": null})
+        )
+    );
+}
+
+#[test]
+fn invalid_request_object() {
+    assert_eq!(
+        call(r#"{"jsonrpc": "2.0", "method": 1, "params": "bar"}"#),
+        Some(
+            json!({"jsonrpc": "2.0", "error": {"code": -32600, "message": "Invalid Request"}, "id": null})
+        )
+    );
+}
+
+#[test]
+fn batch_invalid_json() {
+    assert_eq!(
+        call(
+            r#"[
+                {"jsonrpc": "2.0", "method": "sum", "params": [1,2,4], "id": "1"},
+                {"jsonrpc": "2.0", "method"
+            ]"#
+        ),
+        Some(
+            json!({"jsonrpc": "2.0", "error": {"code": -32700, "message": "Parse error"}, "id": null})
+        )
+    );
+}
+
+#[test]
+fn empty_batch() {
+    assert_eq!(
+        call("[]"),
+        Some(
+            json!({"jsonrpc": "2.0", "error": {"code": -32600, "message": "Invalid Request"}, "id": null})
+        )
+    );
+}
+
+#[test]
+fn invalid_batch() {
+    let invalid = json!({"jsonrpc": "2.0", "error": {"code": -32600, "message": "Invalid Request"}, "id": null});
+
+    assert_eq!(call("[1]"), Some(json!([invalid])));
+    assert_eq!(call("[1,2,3]"), Some(json!([invalid, invalid, invalid])));
+}
+
+#[test]
+fn batch() {
+    assert_eq!(
+        call(
+            r#"[
+                {"jsonrpc": "2.0", "method": "sum", "params": [1,2,4], "id": "1"},
+                {"jsonrpc": "2.0", "method": "notify_hello", "params": [7]},
+                {"jsonrpc": "2.0", "method": "subtract", "params": [42,23], "id": "2"},
+                {"foo": "boo"},
+                {"jsonrpc": "2.0", "method": "foo.get", "params": {"name": "myself"}, "id": "5"},
+                {"jsonrpc": "2.0", "method": "get_data", "id": "9"}
+            ]"#
+        ),
+        Some(sorted(json!([
+            {"jsonrpc": "2.0", "result": 7, "id": "1"},
+            {"jsonrpc": "2.0", "result": 19, "id": "2"},
+            {"jsonrpc": "2.0", "error": {"code": -32600, "message": "Invalid Request"}, "id": null},
+            {"jsonrpc": "2.0", "error": {"code": -32601, "message": "Method not found"}, "id": "5"},
+            {"jsonrpc": "2.0", "result": ["hello", 5], "id": "9"}
+        ])))
+    );
+}
+
+#[test]
+fn batch_all_notifications() {
+    assert_eq!(
+        call(
+            r#"[
+                {"jsonrpc": "2.0", "method": "notify_sum", "params": [1,2,4]},
+                {"jsonrpc": "2.0", "method": "notify_hello", "params": [7]}
+            ]"#
+        ),
+        None
+    );
+}
+
+#[test]
+fn strict_rejections() {
+    let strict = server(true);
+    let invalid = |id: Value| {
+        Some(
+            json!({"jsonrpc": "2.0", "error": {"code": -32600, "message": "Invalid Request"}, "id": id}),
+        )
+    };
+
+    // missing or wrong `jsonrpc`
+    assert_eq!(handle(&strict, r#"{"method": "sum", "params": [1], "id": 1}"#), invalid(json!(1)));
+    assert_eq!(
+        handle(&strict, r#"{"jsonrpc": "1.0", "method": "sum", "params": [1], "id": 1}"#),
+        invalid(json!(1))
+    );
+    // missing `method`
+    assert_eq!(handle(&strict, r#"{"jsonrpc": "2.0", "id": 1}"#), invalid(json!(1)));
+    // non-structured `params`
+    assert_eq!(
+        handle(&strict, r#"{"jsonrpc": "2.0", "method": "get_data", "params": "bar", "id": 1}"#),
+        invalid(json!(1))
+    );
+    assert_eq!(
+        handle(&strict, r#"{"jsonrpc": "2.0", "method": "get_data", "params": null, "id": 1}"#),
+        invalid(json!(1))
+    );
+    // fractional numeric id
+    assert_eq!(
+        handle(&strict, r#"{"jsonrpc": "2.0", "method": "get_data", "id": 1.5}"#),
+        invalid(json!(1.5))
+    );
+    // a `null` id, which would otherwise go unanswered like a notification
+    assert_eq!(
+        handle(&strict, r#"{"jsonrpc": "2.0", "method": "get_data", "id": null}"#),
+        invalid(Value::Null)
+    );
+    // unknown top-level member
+    assert_eq!(
+        handle(&strict, r#"{"jsonrpc": "2.0", "method": "get_data", "id": 1, "extra": true}"#),
+        invalid(json!(1))
+    );
+    // each batch item is checked on its own
+    assert_eq!(
+        handle(
+            &strict,
+            r#"[{"jsonrpc": "2.0", "method": "sum", "params": [1, 2], "id": 1}, {"method": "sum", "params": [1], "id": 2}]"#
+        ),
+        Some(sorted(json!([
+            {"jsonrpc": "2.0", "result": 3, "id": 1},
+            {"jsonrpc": "2.0", "error": {"code": -32600, "message": "Invalid Request"}, "id": 2}
+        ])))
+    );
+}
+
+#[test]
+fn lenient_by_default() {
+    let lenient = server(false);
+
+    assert_eq!(
+        handle(&lenient, r#"{"method": "sum", "params": [1, 2], "id": 1}"#),
+        Some(json!({"jsonrpc": "2.0", "result": 3, "id": 1}))
+    );
+    assert_eq!(
+        handle(&lenient, r#"{"jsonrpc": "2.0", "method": "get_data", "id": 1, "extra": true}"#),
+        Some(json!({"jsonrpc": "2.0", "result": ["hello", 5], "id": 1}))
+    );
+    // without `method` or `id`, this is taken as a notification rather than an invalid request
+    assert_eq!(handle(&lenient, r#"{"foo": "boo"}"#), None);
+}
+
+#[test]
+fn lenient_recovers_ids() {
+    let lenient = server(false);
+    let invalid =
+        json!({"jsonrpc": "2.0", "error": {"code": -32600, "message": "Invalid Request"}, "id": 7});
+
+    // a `method` that is not a string makes the object invalid, but its id is still answered
+    assert_eq!(handle(&lenient, r#"{"method": 5, "id": 7}"#), Some(invalid.clone()));
+    assert_eq!(
+        handle(
+            &lenient,
+            r#"[{"method": 5, "id": 7}, {"jsonrpc": "2.0", "method": "sum", "params": [1, 2], "id": 8}]"#
+        ),
+        Some(sorted(json!([invalid, {"jsonrpc": "2.0", "result": 3, "id": 8}])))
+    );
+}
+
+#[test]
+fn ids_echoed_as_sent() {
+    let lenient = server(false);
+
+    for id in &["18446744073709551615", "123456789012345678901234567890", "1.50", "-0"] {
+        let request =
+            format!(r#"{{"jsonrpc": "2.0", "method": "sum", "params": [1], "id": {}}}"#, id);
+        let response = futures::executor::block_on(lenient.handle(request.as_bytes(), ()));
+        assert_eq!(
+            serde_json::to_string(&response).unwrap(),
+            format!(r#"{{"jsonrpc":"2.0","result":1,"id":{}}}"#, id)
+        );
+    }
+}
@@ -0,0 +1,148 @@
+//! Server-wide and per-method handler deadlines
+
+mod common;
+
+use common::{call, call_method, gate, Gate};
+use jsonrpc_v2::middleware::{Middleware, Next};
+use jsonrpc_v2::request::RequestObject;
+use jsonrpc_v2::{BoxedSerialize, Data, Error, Server};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+async fn hang(_: ()) -> Result<(), Error> {
+    futures::future::pending().await
+}
+
+async fn fast(_: ()) -> Result<&'static str, Error> {
+    Ok("fast")
+}
+
+async fn gated(gate: Data<Gate>, _: ()) -> Result<&'static str, Error> {
+    gate.pass().await;
+    Ok("gated")
+}
+
+/// Counts the requests it passes on
+struct Count(Arc<AtomicUsize>);
+
+#[async_trait::async_trait]
+impl Middleware<()> for Count {
+    async fn handle(
+        &self,
+        req: RequestObject,
+        metadata: (),
+        next: Next<'_, '_, ()>,
+    ) -> Result<BoxedSerialize, Error> {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        next.run(req, metadata).await
+    }
+}
+
+fn timed_out(response: &Value) -> bool {
+    response["error"]["code"] == json!(-32001) && response["error"]["data"]["elapsed_ms"].is_u64()
+}
+
+#[tokio::test]
+async fn default_timeout() {
+    let server = Server::new(vec![])
+        .with_timeout(Duration::from_millis(20))
+        .with_method("hang", hang)
+        .with_method("fast", fast)
+        .finish();
+
+    let response = call(&server, r#"{"jsonrpc": "2.0", "method": "hang", "id": 1}"#).await;
+    assert!(timed_out(&response), "{}", response);
+    assert_eq!(response["id"], json!(1));
+
+    let response = call(&server, r#"{"jsonrpc": "2.0", "method": "fast", "id": 2}"#).await;
+    assert_eq!(response, json!({"jsonrpc": "2.0", "result": "fast", "id": 2}));
+}
+
+#[tokio::test]
+async fn per_method_timeout_overrides_default() {
+    let gate = gate();
+    let server = Server::new(vec![])
+        .with_data(gate.clone())
+        .with_timeout(Duration::from_millis(20))
+        .with_method("hang", hang)
+        .with_method_timeout("gated", gated, Duration::from_secs(60))
+        .with_method_timeout("hang_briefly", hang, Duration::from_millis(1))
+        .finish();
+
+    let long = call(&server, r#"{"jsonrpc": "2.0", "method": "gated", "id": 1}"#);
+    let default = async {
+        // `gated` has been running for longer than the default timeout by the time this returns
+        let response = call(&server, r#"{"jsonrpc": "2.0", "method": "hang", "id": 2}"#).await;
+        gate.release.add_permits(1);
+        response
+    };
+    let (long, default) = futures::join!(long, default);
+
+    assert_eq!(long, json!({"jsonrpc": "2.0", "result": "gated", "id": 1}));
+    assert!(timed_out(&default), "{}", default);
+
+    let response = call(&server, r#"{"jsonrpc": "2.0", "method": "hang_briefly", "id": 3}"#).await;
+    assert!(timed_out(&response), "{}", response);
+}
+
+#[tokio::test]
+async fn timeout_applies_per_batch_item() {
+    let server = Server::new(vec![])
+        .with_timeout(Duration::from_millis(20))
+        .with_method("hang", hang)
+        .with_method("fast", fast)
+        .finish();
+
+    let response = call(
+        &server,
+        r#"[{"jsonrpc": "2.0", "method": "hang", "id": 1}, {"jsonrpc": "2.0", "method": "fast", "id": 2}]"#,
+    )
+    .await;
+    let items = response.as_array().unwrap();
+
+    let hung = items.iter().find(|item| item["id"] == json!(1)).unwrap();
+    assert!(timed_out(hung), "{}", hung);
+    let fast = items.iter().find(|item| item["id"] == json!(2)).unwrap();
+    assert_eq!(fast["result"], json!("fast"));
+}
+
+#[tokio::test]
+async fn per_method_timeout_with_middlewares() {
+    let count = Arc::new(AtomicUsize::new(0));
+    let server = Server::new(vec![])
+        .with_timeout(Duration::from_secs(60))
+        .with_method_middleware_timeout(
+            "hang",
+            hang,
+            vec![Arc::new(Count(Arc::clone(&count)))],
+            Duration::from_millis(10),
+        )
+        .finish();
+
+    let response = call_method(&server, "hang").await;
+    assert!(timed_out(&response), "{}", response);
+    assert_eq!(count.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn registered_at_runtime() {
+    let count = Arc::new(AtomicUsize::new(0));
+    let server = Server::new(vec![]).with_timeout(Duration::from_secs(60)).finish();
+    let registry = server.registry();
+
+    registry.register_with_timeout("hang", hang, Duration::from_millis(10));
+    let response = call_method(&server, "hang").await;
+    assert!(timed_out(&response), "{}", response);
+
+    registry.register_middleware_with_timeout(
+        "hang",
+        hang,
+        vec![Arc::new(Count(Arc::clone(&count)))],
+        Duration::from_millis(10),
+    );
+    let response = call_method(&server, "hang").await;
+    assert!(timed_out(&response), "{}", response);
+    assert_eq!(count.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,757 @@
+//! Loopback tests for the connection-oriented transports
+
+#![cfg(any(
+    feature = "tcp-transport",
+    feature = "stdio-transport",
+    feature = "ws-transport",
+    all(unix, feature = "unix-transport")
+))]
+
+mod common;
+
+use common::{echo, gate, Gate};
+use jsonrpc_v2::pubsub::Sink;
+use jsonrpc_v2::{Data, Error, Server};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Barrier;
+
+async fn meet(barrier: Data<Barrier>, _: ()) -> Result<&'static str, Error> {
+    barrier.wait().await;
+    Ok("met")
+}
+
+async fn slow(gate: Data<Gate>, _: ()) -> Result<&'static str, Error> {
+    gate.pass().await;
+    Ok("done")
+}
+
+async fn count(counter: Data<AtomicUsize>, _: ()) -> Result<(), Error> {
+    counter.fetch_add(1, Ordering::SeqCst);
+    Ok(())
+}
+
+/// Notifies inline for as long as the subscription lasts
+async fn ticks(_: (), _: (), sink: Sink<u64>) -> Result<(), Error> {
+    let mut tick = 0;
+    while sink.notify(tick).await.is_ok() {
+        tick += 1;
+        tokio::time::delay_for(Duration::from_millis(5)).await;
+    }
+    Ok(())
+}
+
+struct Fixture {
+    server: Arc<Server<()>>,
+    gate: Data<Gate>,
+    counter: Data<AtomicUsize>,
+}
+
+fn fixture() -> Fixture {
+    let gate = gate();
+    let counter = Data::new(AtomicUsize::new(0));
+    let server = Server::new(vec![])
+        .with_data(Data::new(Barrier::new(2)))
+        .with_data(gate.clone())
+        .with_data(counter.clone())
+        .with_max_payload_bytes(1024)
+        .with_method("meet", meet)
+        .with_method("slow", slow)
+        .with_method("echo", echo)
+        .with_method("count", count)
+        .with_subscription("subscribe", "unsubscribe", "tick", ticks)
+        .finish();
+
+    Fixture { server, gate, counter }
+}
+
+/// One client connection, with the framing of the transport under test
+#[async_trait::async_trait]
+trait Conn: Send {
+    async fn send(&mut self, msg: &[u8]);
+    /// The next message, or `None` once the server has closed the connection
+    async fn recv(&mut self) -> Option<Value>;
+}
+
+async fn recv<C: Conn + ?Sized>(conn: &mut C) -> Option<Value> {
+    tokio::time::timeout(Duration::from_secs(5), conn.recv())
+        .await
+        .expect("timed out waiting for the server")
+}
+
+async fn several_in_flight<C: Conn + ?Sized>(conn: &mut C) {
+    // each `meet` only completes once the other is running too
+    conn.send(br#"{"jsonrpc": "2.0", "method": "meet", "id": 1}"#).await;
+    conn.send(br#"{"jsonrpc": "2.0", "method": "meet", "id": 2}"#).await;
+
+    let mut ids =
+        vec![recv(conn).await.unwrap()["id"].clone(), recv(conn).await.unwrap()["id"].clone()];
+    ids.sort_by_key(|id| id.to_string());
+    assert_eq!(ids, vec![json!(1), json!(2)]);
+}
+
+async fn silent_notifications<C: Conn + ?Sized>(conn: &mut C, fixture: &Fixture) {
+    conn.send(br#"{"jsonrpc": "2.0", "method": "count"}"#).await;
+    conn.send(br#"[{"jsonrpc": "2.0", "method": "count"}, {"jsonrpc": "2.0", "method": "count"}]"#)
+        .await;
+    conn.send(br#"{"jsonrpc": "2.0", "method": "echo", "params": [3], "id": 3}"#).await;
+
+    assert_eq!(recv(conn).await, Some(json!({"jsonrpc": "2.0", "result": [3], "id": 3})));
+
+    // the notifications ran even though nothing was written for them
+    for _ in 0..100 {
+        if fixture.counter.load(Ordering::SeqCst) == 3 {
+            return;
+        }
+        tokio::time::delay_for(Duration::from_millis(10)).await;
+    }
+    panic!("notifications were not handled");
+}
+
+#[cfg(any(
+    feature = "tcp-transport",
+    feature = "ws-transport",
+    all(unix, feature = "unix-transport")
+))]
+/// Subscribe to `tick`, returning the subscription id once a notification has arrived
+async fn subscribe<C: Conn + ?Sized>(conn: &mut C) -> Value {
+    conn.send(br#"{"jsonrpc": "2.0", "method": "subscribe", "id": 7}"#).await;
+
+    // the id comes first, even though the handler never returns while subscribed
+    let response = recv(conn).await.unwrap();
+    assert_eq!(response["id"], json!(7));
+    let subscription = response["result"].clone();
+
+    let notification = recv(conn).await.unwrap();
+    assert_eq!(notification["method"], json!("tick"));
+    assert_eq!(notification["params"]["subscription"], subscription);
+    subscription
+}
+
+#[cfg(any(
+    feature = "tcp-transport",
+    feature = "ws-transport",
+    all(unix, feature = "unix-transport")
+))]
+async fn subscription<C: Conn + ?Sized>(conn: &mut C) {
+    let subscription = subscribe(conn).await;
+
+    let unsubscribe =
+        json!({"jsonrpc": "2.0", "method": "unsubscribe", "params": [subscription], "id": 8});
+    conn.send(unsubscribe.to_string().as_bytes()).await;
+    loop {
+        let msg = recv(conn).await.unwrap();
+        if msg["id"] == json!(8) {
+            assert_eq!(msg["result"], json!(true));
+            break;
+        }
+    }
+}
+
+async fn malformed_frames<C: Conn + ?Sized>(conn: &mut C) {
+    conn.send(b"{not json").await;
+    assert_eq!(
+        recv(conn).await,
+        Some(
+            json!({"jsonrpc": "2.0", "error": {"code": -32700, "message": "Parse error"}, "id": null})
+        )
+    );
+
+    conn.send(br#"{"jsonrpc": "2.0", "method": "echo", "params": [5], "id": 5}"#).await;
+    assert_eq!(recv(conn).await, Some(json!({"jsonrpc": "2.0", "result": [5], "id": 5})));
+}
+
+/// Frame limit of the transports under test, well above the server's payload limit
+const MAX_FRAME: usize = 64 * 1024;
+
+/// An `echo` request with `len` bytes of params
+fn echo_of_len(len: usize) -> Vec<u8> {
+    json!({"jsonrpc": "2.0", "method": "echo", "params": ["x".repeat(len)], "id": 7})
+        .to_string()
+        .into_bytes()
+}
+
+fn invalid_request(data: &str) -> Option<Value> {
+    Some(json!({
+        "jsonrpc": "2.0",
+        "error": {"code": -32600, "message": "Invalid Request", "data": data},
+        "id": null
+    }))
+}
+
+async fn still_open<C: Conn + ?Sized>(conn: &mut C) {
+    conn.send(br#"{"jsonrpc": "2.0", "method": "echo", "params": [8], "id": 8}"#).await;
+    assert_eq!(recv(conn).await, Some(json!({"jsonrpc": "2.0", "result": [8], "id": 8})));
+}
+
+async fn over_payload_limit<C: Conn + ?Sized>(conn: &mut C) {
+    conn.send(&echo_of_len(2048)).await;
+    assert_eq!(recv(conn).await, invalid_request("payload exceeds the limit of 1024 bytes"));
+    still_open(conn).await;
+}
+
+#[cfg(any(
+    feature = "tcp-transport",
+    feature = "stdio-transport",
+    all(unix, feature = "unix-transport")
+))]
+mod stream {
+    use super::*;
+    use tokio::io::{
+        AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader,
+    };
+
+    /// A client over a byte stream, speaking either framing
+    pub struct StreamConn<R, W> {
+        reader: BufReader<R>,
+        writer: Option<W>,
+        content_length: bool,
+    }
+
+    impl<R, W> StreamConn<R, W>
+    where
+        R: AsyncRead + Unpin + Send,
+        W: AsyncWrite + Unpin + Send,
+    {
+        pub fn new(reader: R, writer: W, content_length: bool) -> Self {
+            StreamConn { reader: BufReader::new(reader), writer: Some(writer), content_length }
+        }
+
+        /// Write bytes as they are, without framing them
+        pub async fn send_raw(&mut self, bytes: &[u8]) {
+            let writer = self.writer.as_mut().unwrap();
+            writer.write_all(bytes).await.unwrap();
+            writer.flush().await.unwrap();
+        }
+
+        /// Signal EOF to the server while still reading its responses
+        pub async fn close_write(&mut self) {
+            let mut writer = self.writer.take().unwrap();
+            writer.shutdown().await.unwrap();
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl<R, W> Conn for StreamConn<R, W>
+    where
+        R: AsyncRead + Unpin + Send,
+        W: AsyncWrite + Unpin + Send,
+    {
+        async fn send(&mut self, msg: &[u8]) {
+            if self.content_length {
+                let header = format!("Content-Length: {}\r\n\r\n", msg.len());
+                self.send_raw(header.as_bytes()).await;
+                self.send_raw(msg).await;
+            } else {
+                self.send_raw(msg).await;
+                self.send_raw(b"\n").await;
+            }
+        }
+
+        async fn recv(&mut self) -> Option<Value> {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line).await.ok()? == 0 {
+                return None;
+            }
+            if !self.content_length {
+                return Some(serde_json::from_str(&line).unwrap());
+            }
+
+            let len = line.trim_start_matches("Content-Length:").trim().parse::<usize>().unwrap();
+            self.reader.read_line(&mut line).await.unwrap();
+            let mut body = vec![0; len];
+            self.reader.read_exact(&mut body).await.unwrap();
+            Some(serde_json::from_slice(&body).unwrap())
+        }
+    }
+
+    pub async fn over_frame_limit_skipped<C: Conn + ?Sized>(conn: &mut C) {
+        conn.send(&echo_of_len(2 * MAX_FRAME)).await;
+        assert_eq!(recv(conn).await, invalid_request("frame exceeds the limit of 65536 bytes"));
+        still_open(conn).await;
+    }
+
+    pub async fn eof_drains<R, W>(conn: &mut StreamConn<R, W>, fixture: &Fixture)
+    where
+        R: AsyncRead + Unpin + Send,
+        W: AsyncWrite + Unpin + Send,
+    {
+        conn.send(br#"{"jsonrpc": "2.0", "method": "slow", "id": 4}"#).await;
+        fixture.gate.started.acquire().await.forget();
+
+        conn.close_write().await;
+        fixture.gate.release.add_permits(1);
+
+        assert_eq!(recv(conn).await, Some(json!({"jsonrpc": "2.0", "result": "done", "id": 4})));
+        assert_eq!(recv(conn).await, None);
+    }
+}
+
+#[cfg(feature = "tcp-transport")]
+mod tcp {
+    use super::stream::*;
+    use super::*;
+    use futures::channel::oneshot;
+    use jsonrpc_v2::cancel::CancellationToken;
+    use jsonrpc_v2::transport::{tcp::TcpTransport, Framing, Listener, StreamTransport};
+    use std::net::SocketAddr;
+    use std::sync::Mutex;
+    use tokio::net::{TcpListener, TcpStream};
+
+    async fn start(
+        server: Arc<Server<()>>,
+        framing: Framing,
+    ) -> (SocketAddr, oneshot::Sender<()>, tokio::task::JoinHandle<std::io::Result<()>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (stop, signal) = oneshot::channel::<()>();
+        let transport =
+            TcpTransport::new(server, |_| ()).with_framing(framing).with_max_frame_bytes(MAX_FRAME);
+        let handle = tokio::spawn(async move {
+            transport
+                .serve_with_shutdown(listener, async move {
+                    let _ = signal.await;
+                })
+                .await
+        });
+        (addr, stop, handle)
+    }
+
+    async fn connect(
+        addr: SocketAddr,
+        framing: Framing,
+    ) -> StreamConn<tokio::net::tcp::OwnedReadHalf, tokio::net::tcp::OwnedWriteHalf> {
+        let (reader, writer) = TcpStream::connect(addr).await.unwrap().into_split();
+        StreamConn::new(reader, writer, framing == Framing::ContentLength)
+    }
+
+    #[tokio::test]
+    async fn lines() {
+        let fixture = fixture();
+        let (addr, _stop, _) = start(Arc::clone(&fixture.server), Framing::Lines).await;
+
+        several_in_flight(&mut connect(addr, Framing::Lines).await).await;
+        silent_notifications(&mut connect(addr, Framing::Lines).await, &fixture).await;
+        subscription(&mut connect(addr, Framing::Lines).await).await;
+        malformed_frames(&mut connect(addr, Framing::Lines).await).await;
+        over_payload_limit(&mut connect(addr, Framing::Lines).await).await;
+        over_frame_limit_skipped(&mut connect(addr, Framing::Lines).await).await;
+        eof_drains(&mut connect(addr, Framing::Lines).await, &fixture).await;
+        // the server outlives the connections closed above
+        malformed_frames(&mut connect(addr, Framing::Lines).await).await;
+    }
+
+    #[tokio::test]
+    async fn content_length() {
+        let fixture = fixture();
+        let framing = Framing::ContentLength;
+        let (addr, _stop, _) = start(Arc::clone(&fixture.server), framing).await;
+
+        several_in_flight(&mut connect(addr, framing).await).await;
+        silent_notifications(&mut connect(addr, framing).await, &fixture).await;
+        malformed_frames(&mut connect(addr, framing).await).await;
+        eof_drains(&mut connect(addr, framing).await, &fixture).await;
+        over_payload_limit(&mut connect(addr, framing).await).await;
+        over_frame_limit_skipped(&mut connect(addr, framing).await).await;
+
+        // a huge announced length is discarded as it arrives rather than allocated
+        let mut conn = connect(addr, framing).await;
+        conn.send_raw(b"Content-Length: 4611686018427387000\r\n\r\n{}").await;
+        conn.close_write().await;
+        assert_eq!(recv(&mut conn).await, None);
+
+        let mut conn = connect(addr, framing).await;
+        conn.send_raw(b"no header separator\r\n\r\n").await;
+        assert_eq!(recv(&mut conn).await, None);
+
+        malformed_frames(&mut connect(addr, framing).await).await;
+    }
+
+    #[tokio::test]
+    async fn in_flight_limit() {
+        const LIMIT: usize = 4;
+        let fixture = fixture();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let transport =
+            TcpTransport::new(Arc::clone(&fixture.server), |_| ()).with_max_in_flight(LIMIT);
+        tokio::spawn(async move { transport.serve(listener).await });
+        let mut conn = connect(addr, Framing::Lines).await;
+
+        for id in 0..LIMIT {
+            conn.send(json!({"jsonrpc": "2.0", "method": "slow", "id": id}).to_string().as_bytes())
+                .await;
+        }
+        for _ in 0..LIMIT {
+            fixture.gate.started.acquire().await.forget();
+        }
+
+        // every slot is taken, so the server does not read this until a request has been answered
+        conn.send(br#"{"jsonrpc": "2.0", "method": "count"}"#).await;
+        tokio::time::delay_for(Duration::from_millis(100)).await;
+        assert_eq!(fixture.counter.load(Ordering::SeqCst), 0);
+
+        fixture.gate.release.add_permits(LIMIT);
+        for _ in 0..LIMIT {
+            assert_eq!(recv(&mut conn).await.unwrap()["result"], json!("done"));
+        }
+        for _ in 0..100 {
+            if fixture.counter.load(Ordering::SeqCst) == 1 {
+                return;
+            }
+            tokio::time::delay_for(Duration::from_millis(10)).await;
+        }
+        panic!("the notification was not handled");
+    }
+
+    /// Listener that fails every accept, as when the process is out of file descriptors
+    struct Exhausted(Arc<AtomicUsize>);
+
+    impl Listener for Exhausted {
+        type Conn = TcpStream;
+        type Peer = ();
+
+        fn incoming(&mut self) -> impl futures::Stream<Item = std::io::Result<TcpStream>> + '_ {
+            futures::stream::repeat_with(move || {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                Err(std::io::Error::other("too many open files"))
+            })
+        }
+        fn peer(_: &TcpStream) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn accept_errors_back_off() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let transport = StreamTransport::new(fixture().server, |_| ());
+
+        let signal = tokio::time::delay_for(Duration::from_millis(200));
+        transport.serve_with_shutdown(Exhausted(Arc::clone(&attempts)), signal).await.unwrap();
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn shutdown_drains() {
+        let fixture = fixture();
+        let (addr, stop, handle) = start(Arc::clone(&fixture.server), Framing::Lines).await;
+        let mut conn = connect(addr, Framing::Lines).await;
+
+        conn.send(br#"{"jsonrpc": "2.0", "method": "slow", "id": 6}"#).await;
+        fixture.gate.started.acquire().await.forget();
+
+        stop.send(()).unwrap();
+        fixture.gate.release.add_permits(1);
+
+        assert_eq!(
+            recv(&mut conn).await,
+            Some(json!({"jsonrpc": "2.0", "result": "done", "id": 6}))
+        );
+        assert_eq!(recv(&mut conn).await, None);
+        tokio::time::timeout(Duration::from_secs(5), handle).await.unwrap().unwrap().unwrap();
+    }
+
+    /// Never completes; keeps the cancellation token of each call
+    async fn hang(
+        token: CancellationToken,
+        hung: Data<Mutex<Vec<CancellationToken>>>,
+        _: (),
+    ) -> Result<(), Error> {
+        hung.lock().unwrap().push(token);
+        futures::future::pending().await
+    }
+
+    #[tokio::test]
+    async fn cancellation() {
+        let fixture = fixture();
+        let hung = Data::new(Mutex::new(Vec::<CancellationToken>::new()));
+        let server = Server::new(vec![])
+            .with_data(fixture.gate.clone())
+            .with_data(hung.clone())
+            .with_method("slow", slow)
+            .with_method("hang", hang)
+            .finish();
+        let (addr, _stop, _) = start(server, Framing::Lines).await;
+        let mut conn = connect(addr, Framing::Lines).await;
+
+        conn.send(br#"{"jsonrpc": "2.0", "method": "hang", "id": 1}"#).await;
+        while hung.lock().unwrap().is_empty() {
+            tokio::time::delay_for(Duration::from_millis(1)).await;
+        }
+        conn.send(br#"{"jsonrpc": "2.0", "method": "$/cancelRequest", "params": {"id": 1}}"#).await;
+        assert_eq!(
+            recv(&mut conn).await,
+            Some(
+                json!({"jsonrpc": "2.0", "error": {"code": -32800, "message": "Request cancelled"}, "id": 1})
+            )
+        );
+        assert!(hung.lock().unwrap()[0].is_cancelled());
+
+        // a request reusing an in-flight id takes over the id, even once the first one completes
+        conn.send(br#"{"jsonrpc": "2.0", "method": "slow", "id": 2}"#).await;
+        fixture.gate.started.acquire().await.forget();
+        conn.send(br#"{"jsonrpc": "2.0", "method": "hang", "id": 2}"#).await;
+        while hung.lock().unwrap().len() < 2 {
+            tokio::time::delay_for(Duration::from_millis(1)).await;
+        }
+        fixture.gate.release.add_permits(1);
+        assert_eq!(
+            recv(&mut conn).await,
+            Some(json!({"jsonrpc": "2.0", "result": "done", "id": 2}))
+        );
+
+        conn.send(
+            br#"{"jsonrpc": "2.0", "method": "$/cancelRequest", "params": {"id": 2}, "id": 3}"#,
+        )
+        .await;
+        let mut replies = vec![recv(&mut conn).await.unwrap(), recv(&mut conn).await.unwrap()];
+        replies.sort_by_key(|reply| reply["id"].to_string());
+        assert_eq!(
+            replies,
+            vec![
+                json!({"jsonrpc": "2.0", "error": {"code": -32800, "message": "Request cancelled"}, "id": 2}),
+                json!({"jsonrpc": "2.0", "result": true, "id": 3}),
+            ]
+        );
+        assert!(hung.lock().unwrap()[1].is_cancelled());
+
+        // nothing is left in flight under that id
+        conn.send(
+            br#"{"jsonrpc": "2.0", "method": "$/cancelRequest", "params": {"id": 2}, "id": 4}"#,
+        )
+        .await;
+        assert_eq!(
+            recv(&mut conn).await,
+            Some(json!({"jsonrpc": "2.0", "result": false, "id": 4}))
+        );
+    }
+
+    #[tokio::test]
+    async fn shutdown_ends_subscriptions() {
+        let fixture = fixture();
+        let (addr, stop, handle) = start(Arc::clone(&fixture.server), Framing::Lines).await;
+        let mut conn = connect(addr, Framing::Lines).await;
+        subscribe(&mut conn).await;
+
+        stop.send(()).unwrap();
+
+        while recv(&mut conn).await.is_some() {}
+        tokio::time::timeout(Duration::from_secs(5), handle).await.unwrap().unwrap().unwrap();
+    }
+}
+
+#[cfg(all(unix, feature = "unix-transport"))]
+mod unix {
+    use super::stream::*;
+    use super::*;
+    use futures::channel::oneshot;
+    use jsonrpc_v2::transport::unix::UnixTransport;
+    use std::path::PathBuf;
+    use tokio::net::{UnixListener, UnixStream};
+
+    fn socket_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("jsonrpc-v2-{}-{}.sock", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    async fn connect(
+        path: &PathBuf,
+    ) -> StreamConn<tokio::net::unix::OwnedReadHalf, tokio::net::unix::OwnedWriteHalf> {
+        let (reader, writer) = UnixStream::connect(path).await.unwrap().into_split();
+        StreamConn::new(reader, writer, false)
+    }
+
+    #[tokio::test]
+    async fn lines() {
+        let fixture = fixture();
+        let path = socket_path("lines");
+        let listener = UnixListener::bind(&path).unwrap();
+        let (stop, signal) = oneshot::channel::<()>();
+        let transport =
+            UnixTransport::new(Arc::clone(&fixture.server), |_| ()).with_max_frame_bytes(MAX_FRAME);
+        let handle = tokio::spawn(async move {
+            transport
+                .serve_with_shutdown(listener, async move {
+                    let _ = signal.await;
+                })
+                .await
+        });
+
+        several_in_flight(&mut connect(&path).await).await;
+        silent_notifications(&mut connect(&path).await, &fixture).await;
+        subscription(&mut connect(&path).await).await;
+        malformed_frames(&mut connect(&path).await).await;
+        over_payload_limit(&mut connect(&path).await).await;
+        over_frame_limit_skipped(&mut connect(&path).await).await;
+        eof_drains(&mut connect(&path).await, &fixture).await;
+
+        let mut conn = connect(&path).await;
+        conn.send(br#"{"jsonrpc": "2.0", "method": "slow", "id": 6}"#).await;
+        fixture.gate.started.acquire().await.forget();
+        stop.send(()).unwrap();
+        fixture.gate.release.add_permits(1);
+        assert_eq!(
+            recv(&mut conn).await,
+            Some(json!({"jsonrpc": "2.0", "result": "done", "id": 6}))
+        );
+        assert_eq!(recv(&mut conn).await, None);
+        tokio::time::timeout(Duration::from_secs(5), handle).await.unwrap().unwrap().unwrap();
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
+
+#[cfg(feature = "stdio-transport")]
+mod stdio {
+    use super::stream::*;
+    use super::*;
+    use jsonrpc_v2::transport::{stdio::StdioTransport, Framing};
+    use tokio::net::{TcpListener, TcpStream};
+
+    /// Serve the stdio transport over one end of a loopback socket, returning the other end
+    async fn start(
+        server: Arc<Server<()>>,
+        framing: Framing,
+    ) -> (
+        StreamConn<tokio::net::tcp::OwnedReadHalf, tokio::net::tcp::OwnedWriteHalf>,
+        tokio::task::JoinHandle<std::io::Result<()>>,
+    ) {
+        let mut listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (served, _) = listener.accept().await.unwrap();
+
+        let handle = tokio::spawn(async move {
+            let (reader, writer) = served.into_split();
+            StdioTransport::new(server, ())
+                .with_framing(framing)
+                .with_max_frame_bytes(MAX_FRAME)
+                .serve_io(reader, writer)
+                .await
+        });
+        let (reader, writer) = client.into_split();
+        (StreamConn::new(reader, writer, framing == Framing::ContentLength), handle)
+    }
+
+    #[tokio::test]
+    async fn content_length() {
+        let fixture = fixture();
+        let (mut conn, handle) = start(Arc::clone(&fixture.server), Framing::ContentLength).await;
+
+        several_in_flight(&mut conn).await;
+        silent_notifications(&mut conn, &fixture).await;
+        malformed_frames(&mut conn).await;
+        over_payload_limit(&mut conn).await;
+        over_frame_limit_skipped(&mut conn).await;
+        eof_drains(&mut conn, &fixture).await;
+        tokio::time::timeout(Duration::from_secs(5), handle).await.unwrap().unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn oversized_line_skipped() {
+        let fixture = fixture();
+        let (mut conn, handle) = start(Arc::clone(&fixture.server), Framing::Lines).await;
+
+        over_frame_limit_skipped(&mut conn).await;
+        // one byte over the limit, so it is read whole before it is found too long
+        conn.send(&echo_of_len(MAX_FRAME + 1 - echo_of_len(0).len())).await;
+        assert_eq!(
+            recv(&mut conn).await,
+            invalid_request("frame exceeds the limit of 65536 bytes")
+        );
+
+        // an unterminated line over the limit is answered before the end of input ends serving
+        conn.send_raw(&echo_of_len(2 * MAX_FRAME)).await;
+        conn.close_write().await;
+        assert_eq!(
+            recv(&mut conn).await,
+            invalid_request("frame exceeds the limit of 65536 bytes")
+        );
+        assert_eq!(recv(&mut conn).await, None);
+        tokio::time::timeout(Duration::from_secs(5), handle).await.unwrap().unwrap().unwrap();
+    }
+}
+
+#[cfg(feature = "ws-transport")]
+mod ws {
+    use super::*;
+    use futures::channel::oneshot;
+    use futures::{SinkExt, StreamExt};
+    use jsonrpc_v2::transport::ws::WsTransport;
+    use std::net::SocketAddr;
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_tungstenite::tungstenite::Message;
+    use tokio_tungstenite::WebSocketStream;
+
+    struct WsConn(WebSocketStream<TcpStream>);
+
+    async fn over_frame_limit_closes<C: Conn + ?Sized>(conn: &mut C) {
+        conn.send(&echo_of_len(2 * MAX_FRAME)).await;
+        assert_eq!(recv(conn).await, None);
+    }
+
+    #[async_trait::async_trait]
+    impl Conn for WsConn {
+        async fn send(&mut self, msg: &[u8]) {
+            self.0.send(Message::Binary(msg.to_vec())).await.unwrap();
+        }
+
+        async fn recv(&mut self) -> Option<Value> {
+            loop {
+                match self.0.next().await? {
+                    Ok(Message::Text(text)) => return Some(serde_json::from_str(&text).unwrap()),
+                    Ok(Message::Close(_)) | Err(_) => return None,
+                    Ok(_) => {}
+                }
+            }
+        }
+    }
+
+    async fn connect(addr: SocketAddr) -> WsConn {
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let url = format!("ws://{}/", addr);
+        let (ws, _) = tokio_tungstenite::client_async(url.as_str(), stream).await.unwrap();
+        WsConn(ws)
+    }
+
+    #[tokio::test]
+    async fn messages() {
+        let fixture = fixture();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (stop, signal) = oneshot::channel::<()>();
+        let transport = WsTransport::new(Arc::clone(&fixture.server), |_, _| ())
+            .with_max_message_bytes(MAX_FRAME);
+        let handle = tokio::spawn(async move {
+            transport
+                .serve_with_shutdown(listener, async move {
+                    let _ = signal.await;
+                })
+                .await
+        });
+
+        several_in_flight(&mut connect(addr).await).await;
+        silent_notifications(&mut connect(addr).await, &fixture).await;
+        subscription(&mut connect(addr).await).await;
+        malformed_frames(&mut connect(addr).await).await;
+        over_payload_limit(&mut connect(addr).await).await;
+        over_frame_limit_closes(&mut connect(addr).await).await;
+        malformed_frames(&mut connect(addr).await).await;
+
+        let mut conn = connect(addr).await;
+        conn.send(br#"{"jsonrpc": "2.0", "method": "slow", "id": 6}"#).await;
+        fixture.gate.started.acquire().await.forget();
+        stop.send(()).unwrap();
+        fixture.gate.release.add_permits(1);
+        assert_eq!(
+            recv(&mut conn).await,
+            Some(json!({"jsonrpc": "2.0", "result": "done", "id": 6}))
+        );
+        assert_eq!(recv(&mut conn).await, None);
+        tokio::time::timeout(Duration::from_secs(5), handle).await.unwrap().unwrap().unwrap();
+    }
+}